@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+// Fractional sample positions a step can start at.
+const PHASE_BITS: u32 = 6;
+const PHASES: usize = 1 << PHASE_BITS;
+// Output samples each step is spread over, which is also the buffer's delay.
+const KERNEL_WIDTH: usize = 16;
+// Where the kernel's response is centred, as a fraction of the output Nyquist frequency.
+const CUTOFF: f64 = 0.9;
+const FRAC_BITS: u32 = 32;
+
+/// Band-limited step synthesis. The input is a level that changes at exact clock times, and each
+/// change is added to the output as a band-limited step, so edges don't alias and nothing has to
+/// be done on the clocks where the level stays the same.
+///
+/// Time is counted in clocks from the start of the current frame. `end_frame` makes every sample
+/// before that point available to `read_samples`.
+pub struct BlipBuffer {
+    // Output samples per clock in 32.32 fixed point.
+    factor: u64,
+    // The start of the frame in output samples since the first unread one, in 32.32 fixed point.
+    offset: u64,
+    // Differences between consecutive output samples, starting from the first unread one.
+    deltas: Vec<f32>,
+    // The last sample read, which the differences are summed onto.
+    level: f32,
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        let mut buffer = Self {
+            factor: 0,
+            offset: 0,
+            deltas: Vec::new(),
+            level: 0.0,
+            kernel: (0..PHASES).map(kernel_phase).collect(),
+        };
+        buffer.set_rates(clock_rate, sample_rate);
+        buffer
+    }
+
+    /// Changes how clocks map to samples from the next frame on.
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.factor = (sample_rate / clock_rate * (1u64 << FRAC_BITS) as f64).round() as u64;
+    }
+
+    /// Adds a change of `delta` to the level at `time` clocks into the frame.
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let position = self.offset + time as u64 * self.factor;
+        let index = (position >> FRAC_BITS) as usize;
+        let phase = (position >> (FRAC_BITS - PHASE_BITS)) as usize & (PHASES - 1);
+
+        if self.deltas.len() < index + KERNEL_WIDTH {
+            self.deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+        for (output, weight) in self.deltas[index..].iter_mut().zip(&self.kernel[phase]) {
+            *output += delta * weight;
+        }
+    }
+
+    /// Ends the frame `clocks` long, and starts the next one there.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as u64 * self.factor;
+    }
+
+    /// Samples that can be read, those whose steps can all have been added already.
+    pub fn samples_available(&self) -> usize {
+        (self.offset >> FRAC_BITS) as usize
+    }
+
+    /// Reads every available sample into `output`.
+    pub fn read_samples(&mut self, output: &mut Vec<f32>) {
+        let count = self.samples_available();
+        if self.deltas.len() < count {
+            self.deltas.resize(count, 0.0);
+        }
+        for delta in self.deltas.drain(..count) {
+            self.level += delta;
+            output.push(self.level);
+        }
+        self.offset -= (count as u64) << FRAC_BITS;
+    }
+}
+
+// A Blackman-windowed sinc starting `phase / PHASES` of a sample late, scaled so that a step's
+// weights add up to its full height.
+fn kernel_phase(phase: usize) -> [f32; KERNEL_WIDTH] {
+    let shift = phase as f64 / PHASES as f64;
+    let mut weights = [0.0; KERNEL_WIDTH];
+    for (i, weight) in weights.iter_mut().enumerate() {
+        let x = i as f64 - (KERNEL_WIDTH / 2) as f64 - shift;
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+        };
+        let window_position = (x + (KERNEL_WIDTH / 2) as f64) / KERNEL_WIDTH as f64;
+        let window = 0.42 - 0.5 * (2.0 * PI * window_position).cos()
+            + 0.08 * (4.0 * PI * window_position).cos();
+        *weight = sinc * window;
+    }
+
+    let sum: f64 = weights.iter().sum();
+    weights.map(|weight| (weight / sum) as f32)
+}
@@ -0,0 +1,69 @@
+use std::f32::consts::PI;
+
+/// The filters between the console's DAC and its audio output: high-passes at 90 Hz and 440 Hz
+/// and a low-pass at 14 kHz, each first order.
+pub struct OutputFilter {
+    high_passes: [HighPass; 2],
+    low_pass: LowPass,
+}
+
+impl OutputFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            high_passes: [
+                HighPass::new(90.0, sample_rate),
+                HighPass::new(440.0, sample_rate),
+            ],
+            low_pass: LowPass::new(14000.0, sample_rate),
+        }
+    }
+
+    pub fn apply(&mut self, sample: f32) -> f32 {
+        let sample = self.high_passes[0].apply(sample);
+        let sample = self.high_passes[1].apply(sample);
+        self.low_pass.apply(sample)
+    }
+}
+
+struct HighPass {
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl HighPass {
+    fn new(cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        Self {
+            alpha: rc / (rc + 1.0 / sample_rate),
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    fn apply(&mut self, input: f32) -> f32 {
+        self.previous_output = self.alpha * (self.previous_output + input - self.previous_input);
+        self.previous_input = input;
+        self.previous_output
+    }
+}
+
+struct LowPass {
+    alpha: f32,
+    previous_output: f32,
+}
+
+impl LowPass {
+    fn new(cutoff: f32, sample_rate: f32) -> Self {
+        let dt = 1.0 / sample_rate;
+        Self {
+            alpha: dt / (1.0 / (2.0 * PI * cutoff) + dt),
+            previous_output: 0.0,
+        }
+    }
+
+    fn apply(&mut self, input: f32) -> f32 {
+        self.previous_output += self.alpha * (input - self.previous_output);
+        self.previous_output
+    }
+}
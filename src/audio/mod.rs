@@ -1,61 +1,168 @@
-use std::thread::yield_now;
+use std::time::Duration;
 
-use sdl3::audio::{AudioFormat, AudioSpec, AudioStreamOwner};
+use crate::core::Region;
 
-mod resampler;
-use resampler::Resampler;
+mod blip;
+mod filter;
+mod sink;
+mod wav;
+use blip::BlipBuffer;
+use filter::OutputFilter;
+pub use sink::*;
+pub use wav::WavWriter;
 
-const AUDIO_FREQUENCY: usize = 48000;
-const TARGET_SAMPLE_SIZE: usize = 1789773 / AUDIO_FREQUENCY;
-const TARGET_BUFFER_SIZE: usize = AUDIO_FREQUENCY / 8;
-const MIN_BUFFER_SIZE: usize = TARGET_BUFFER_SIZE / 2;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+pub const MAX_SAMPLE_RATE: u32 = 192000;
+/// How far ahead of the sound card audio is kept by default.
+pub const DEFAULT_LATENCY: Duration = Duration::from_millis(60);
+const MIN_LATENCY: Duration = Duration::from_millis(10);
+const MAX_LATENCY: Duration = Duration::from_millis(500);
+// CPU cycles between sending audio to the sink, about 2 ms.
+const FLUSH_PERIOD: u32 = 4096;
+// Largest change to the resampling ratio dynamic rate control may make, as a fraction. Small
+// enough that the pitch change can't be heard.
+const MAX_RATE_ADJUSTMENT: f64 = 0.005;
 
+/// Sends APU samples to an `AudioSink`. Emulation is paced elsewhere, so when more audio is
+/// produced than the device can play it is dropped instead of waited on.
+///
+/// The APU's left and right levels are turned into samples at the sink's rate by band-limited
+/// synthesis, then shaped by the same filters as the console's output.
+///
+/// The emulator and sound card clocks never quite agree, especially when frames are synced to
+/// the display. Dynamic rate control makes up the difference by stretching the audio slightly
+/// whenever the device's queue drifts from its target level.
 pub struct AudioOutput {
-    sound_sampler: AudioStreamOwner,
-    resampler: Resampler,
+    sink: Box<dyn AudioSink>,
+    // Left then right.
+    blips: [BlipBuffer; 2],
+    filters: [OutputFilter; 2],
+    levels: [f32; 2],
+    speed: Option<f32>,
+    clock_rate: u32,
+    // What the blips resample to, the sink's rate adjusted for speed and drift.
+    sample_rate: f64,
+    // Frames the sink's queue is kept at.
+    target_frames: usize,
+    // Cycles since the last flush.
+    clocks: u32,
+    samples: [Vec<f32>; 2],
 }
 
 impl Default for AudioOutput {
+    /// Silent, until a real sink is given with `Nes::set_audio_sink`.
     fn default() -> Self {
-        let sdl_context = sdl3::init().unwrap();
-        let audio_subsystem = sdl_context.audio().unwrap();
-
-        let desired_spec = AudioSpec {
-            freq: Some(AUDIO_FREQUENCY as i32),
-            channels: Some(2),
-            format: Some(AudioFormat::f32_sys()),
-        };
-
-        let device = audio_subsystem.open_playback_device(&desired_spec).unwrap();
-        let sound_sampler = device.open_device_stream(Some(&desired_spec)).unwrap();
-        sound_sampler.resume().unwrap();
-
-        Self {
-            sound_sampler,
-            resampler: Resampler::default(),
-        }
+        Self::new(
+            Box::new(NullSink::new(DEFAULT_SAMPLE_RATE)),
+            DEFAULT_LATENCY,
+            Region::default(),
+        )
     }
 }
 
 impl AudioOutput {
-    pub fn push_sample(&mut self, sample: f32) {
-        self.resampler.push(sample);
+    /// `latency` is how much audio is queued ahead of the device, from 10 to 500 ms. Samples are
+    /// pushed at `region`'s CPU clock.
+    pub fn new(sink: Box<dyn AudioSink>, latency: Duration, region: Region) -> Self {
+        let clock_rate = region.cpu_clock();
+        let sample_rate = sink.sample_rate();
+        let latency = latency.clamp(MIN_LATENCY, MAX_LATENCY);
+        Self {
+            blips: [(); 2].map(|_| BlipBuffer::new(clock_rate as f64, sample_rate as f64)),
+            filters: [(); 2].map(|_| OutputFilter::new(sample_rate as f32)),
+            levels: [0.0; 2],
+            speed: Some(1.0),
+            clock_rate,
+            sample_rate: sample_rate as f64,
+            target_frames: (latency.as_secs_f64() * sample_rate as f64) as usize,
+            clocks: 0,
+            samples: Default::default(),
+            sink,
+        }
+    }
 
-        let queued_samples = self.sound_sampler.queued_bytes().unwrap() as usize / size_of::<f32>();
-        if self.resampler.len() > MIN_BUFFER_SIZE && queued_samples < TARGET_BUFFER_SIZE {
-            self.flush_audio();
+    /// The rate samples are pushed at, which is the region's CPU clock.
+    pub fn set_clock_rate(&mut self, clock_rate: u32) {
+        // Cycles already pushed were at the old rate.
+        if let (Some(speed), 1..) = (self.speed, self.clocks) {
+            self.flush_audio(speed);
         }
+        self.clock_rate = clock_rate;
+        for blip in &mut self.blips {
+            blip.set_rates(clock_rate as f64, self.sample_rate);
+        }
+    }
+
+    /// Stretches the audio for emulation running at `speed` times normal, lowering the pitch in
+    /// slow motion. `None` drops all audio, for running unthrottled.
+    pub fn set_speed(&mut self, speed: Option<f32>) {
+        self.speed = speed;
+    }
 
-        while self.sound_sampler.queued_bytes().unwrap() as usize / size_of::<f32>()
-            > TARGET_BUFFER_SIZE / 2
+    /// Takes the APU's left and right output levels for one CPU cycle. Only changes in them cost
+    /// anything.
+    pub fn push_sample(&mut self, left: f32, right: f32) {
+        let Some(speed) = self.speed else {
+            return;
+        };
+        for ((blip, level), sample) in self
+            .blips
+            .iter_mut()
+            .zip(&mut self.levels)
+            .zip([left, right])
         {
-            yield_now();
+            if sample != *level {
+                blip.add_delta(self.clocks, sample - *level);
+                *level = sample;
+            }
+        }
+
+        self.clocks += 1;
+        if self.clocks >= FLUSH_PERIOD {
+            self.flush_audio(speed);
         }
     }
 
-    fn flush_audio(&mut self) {
-        let desired_samples = self.resampler.len() / TARGET_SAMPLE_SIZE;
-        let resampled_audio = self.resampler.resample(desired_samples);
-        self.sound_sampler.put_data_f32(&resampled_audio).unwrap();
+    /// Completes the sink's output, e.g. a WAV file's header.
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.sink.finish()
+    }
+
+    fn flush_audio(&mut self, speed: f32) {
+        for ((blip, filter), samples) in self
+            .blips
+            .iter_mut()
+            .zip(&mut self.filters)
+            .zip(&mut self.samples)
+        {
+            blip.end_frame(self.clocks);
+            samples.clear();
+            blip.read_samples(samples);
+            for sample in samples.iter_mut() {
+                *sample = filter.apply(*sample);
+            }
+        }
+        self.clocks = 0;
+
+        let queued_frames = self.sink.queued_frames();
+        if queued_frames.is_none_or(|queued| queued < self.target_frames * 2) {
+            let [left, right] = &self.samples;
+            let output: Vec<f32> = left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect();
+            if let Err(e) = self.sink.write(&output) {
+                eprintln!("{e}, continuing without sound");
+                self.sink = Box::new(NullSink::new(self.sink.sample_rate()));
+            }
+        }
+
+        // The next frame's samples are stretched to bring the queue back to its target.
+        let rate = queued_frames.map_or(1.0, |queued| {
+            let drift = (1.0 - queued as f64 / self.target_frames as f64).clamp(-1.0, 1.0);
+            1.0 + drift * MAX_RATE_ADJUSTMENT
+        });
+        self.sample_rate = self.sink.sample_rate() as f64 * rate / speed as f64;
+        for blip in &mut self.blips {
+            blip.set_rates(self.clock_rate as f64, self.sample_rate);
+        }
     }
 }
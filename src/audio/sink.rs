@@ -0,0 +1,179 @@
+use std::{path::Path, str::FromStr};
+
+use sdl3::audio::{AudioFormat, AudioSpec, AudioStreamOwner};
+use serde::{Deserialize, Serialize};
+
+use super::WavWriter;
+
+/// Where `AudioOutput` sends its samples, which are interleaved stereo.
+pub trait AudioSink {
+    fn sample_rate(&self) -> u32;
+
+    /// Stereo frames written but not played yet. `None` for sinks that take samples as fast as
+    /// they come, which then need no rate control.
+    fn queued_frames(&self) -> Option<usize>;
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String>;
+
+    /// Completes anything written, e.g. a file's header.
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioDriver {
+    /// The default sound device.
+    #[default]
+    Sdl,
+    /// A `.wav` file of what would have been played.
+    Wav,
+    /// Nothing.
+    Null,
+}
+
+impl FromStr for AudioDriver {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "sdl" => Ok(AudioDriver::Sdl),
+            "wav" => Ok(AudioDriver::Wav),
+            "null" => Ok(AudioDriver::Null),
+            _ => Err(format!(
+                "Unknown audio driver '{name}', expected sdl, wav or null"
+            )),
+        }
+    }
+}
+
+/// Opens `driver` at `sample_rate`, writing to `wav_path` for `AudioDriver::Wav`. Falls back to
+/// `NullSink` with a warning if it can't be opened, so a missing sound device doesn't stop the
+/// game.
+pub fn open_sink(driver: AudioDriver, sample_rate: u32, wav_path: &Path) -> Box<dyn AudioSink> {
+    let sink: Result<Box<dyn AudioSink>, String> = match driver {
+        AudioDriver::Sdl => SdlSink::open(sample_rate).map(|sink| Box::new(sink) as _),
+        AudioDriver::Wav => WavSink::create(wav_path, sample_rate).map(|sink| Box::new(sink) as _),
+        AudioDriver::Null => Ok(Box::new(NullSink::new(sample_rate))),
+    };
+    sink.unwrap_or_else(|e| {
+        eprintln!("Warning: {e}, continuing without sound");
+        Box::new(NullSink::new(sample_rate))
+    })
+}
+
+/// Plays through the default device with SDL.
+pub struct SdlSink {
+    stream: AudioStreamOwner,
+    sample_rate: u32,
+}
+
+impl SdlSink {
+    pub fn open(sample_rate: u32) -> Result<Self, String> {
+        let error = |e: sdl3::Error| format!("Unable to open audio device: {e}");
+        let sdl_context = sdl3::init().map_err(error)?;
+        let audio_subsystem = sdl_context.audio().map_err(error)?;
+
+        let desired_spec = AudioSpec {
+            freq: Some(sample_rate as i32),
+            channels: Some(2),
+            format: Some(AudioFormat::f32_sys()),
+        };
+
+        let device = audio_subsystem
+            .open_playback_device(&desired_spec)
+            .map_err(error)?;
+        let stream = device
+            .open_device_stream(Some(&desired_spec))
+            .map_err(error)?;
+        stream.resume().map_err(error)?;
+
+        Ok(Self {
+            stream,
+            sample_rate,
+        })
+    }
+}
+
+impl AudioSink for SdlSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn queued_frames(&self) -> Option<usize> {
+        let queued_bytes = self.stream.queued_bytes().unwrap_or(0).max(0) as usize;
+        Some(queued_bytes / (2 * size_of::<f32>()))
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        self.stream
+            .put_data_f32(samples)
+            .map_err(|e| format!("Unable to play audio: {e}"))
+    }
+}
+
+/// Writes 16-bit stereo to a `.wav` file.
+pub struct WavSink {
+    writer: Option<WavWriter>,
+    sample_rate: u32,
+}
+
+impl WavSink {
+    pub fn create(path: &Path, sample_rate: u32) -> Result<Self, String> {
+        Ok(Self {
+            writer: Some(WavWriter::create(path, sample_rate, 2)?),
+            sample_rate,
+        })
+    }
+}
+
+impl AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn queued_frames(&self) -> Option<usize> {
+        None
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let samples: Vec<i16> = samples
+            .iter()
+            .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        writer.write_samples(&samples)
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.writer.take().map_or(Ok(()), WavWriter::finish)
+    }
+}
+
+/// Discards everything.
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn queued_frames(&self) -> Option<usize> {
+        None
+    }
+
+    fn write(&mut self, _samples: &[f32]) -> Result<(), String> {
+        Ok(())
+    }
+}
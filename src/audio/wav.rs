@@ -0,0 +1,77 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const HEADER_SIZE: u32 = 44;
+
+/// Writes 16-bit PCM to a `.wav` file. The header's sizes are filled in by `finish`.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    data_size: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            channels,
+            data_size: 0,
+        };
+
+        let block_align = channels * 2;
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(HEADER_SIZE - 8).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        writer.write(&header)?;
+        Ok(writer)
+    }
+
+    /// Appends samples, interleaved if there is more than one channel. Fails without writing
+    /// anything once the file would pass the 4 GB a WAV file can hold.
+    pub fn write_samples(&mut self, samples: &[i16]) -> Result<(), String> {
+        debug_assert!(samples.len().is_multiple_of(self.channels as usize));
+        let data_size = u32::try_from(samples.len() * 2)
+            .ok()
+            .and_then(|size| size.checked_add(self.data_size))
+            .filter(|size| size.checked_add(HEADER_SIZE - 8).is_some())
+            .ok_or("Unable to write audio: the WAV file is full")?;
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.write(&bytes)?;
+        self.data_size = data_size;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let data_size = self.data_size;
+        self.patch(4, HEADER_SIZE - 8 + data_size)
+            .and_then(|_| self.patch(HEADER_SIZE as u64 - 4, data_size))
+            .and_then(|_| self.file.flush())
+            .map_err(|e| format!("Unable to write audio: {e}"))
+    }
+
+    fn patch(&mut self, position: u64, value: u32) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file.write_all(&value.to_le_bytes())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.file
+            .write_all(bytes)
+            .map_err(|e| format!("Unable to write audio: {e}"))
+    }
+}
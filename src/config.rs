@@ -0,0 +1,169 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    audio::{AudioDriver, DEFAULT_LATENCY, DEFAULT_SAMPLE_RATE},
+    core::{Channel, ChannelMix, PlayerBindings},
+    window::{Overscan, PixelAspect},
+};
+
+/// Keys for the emulator itself rather than the game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub save_state: Option<VirtualKeyCode>,
+    pub load_state: Option<VirtualKeyCode>,
+    pub reset: Option<VirtualKeyCode>,
+    pub pause: Option<VirtualKeyCode>,
+    pub frame_advance: Option<VirtualKeyCode>,
+    pub fast_forward: Option<VirtualKeyCode>,
+    pub slow_motion: Option<VirtualKeyCode>,
+    pub rewind: Option<VirtualKeyCode>,
+    pub ppu_view: Option<VirtualKeyCode>,
+    pub shader: Option<VirtualKeyCode>,
+    pub fullscreen: Option<VirtualKeyCode>,
+    pub pixel_aspect: Option<VirtualKeyCode>,
+    pub overscan: Option<VirtualKeyCode>,
+    pub integer_scaling: Option<VirtualKeyCode>,
+    pub screenshot: Option<VirtualKeyCode>,
+    pub record_video: Option<VirtualKeyCode>,
+    pub record_audio: Option<VirtualKeyCode>,
+    pub volume_up: Option<VirtualKeyCode>,
+    pub volume_down: Option<VirtualKeyCode>,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            save_state: Some(VirtualKeyCode::F5),
+            load_state: Some(VirtualKeyCode::F7),
+            reset: Some(VirtualKeyCode::F2),
+            pause: Some(VirtualKeyCode::P),
+            frame_advance: Some(VirtualKeyCode::Backslash),
+            fast_forward: Some(VirtualKeyCode::Tab),
+            slow_motion: Some(VirtualKeyCode::F3),
+            rewind: Some(VirtualKeyCode::Back),
+            ppu_view: Some(VirtualKeyCode::F9),
+            shader: Some(VirtualKeyCode::F8),
+            fullscreen: Some(VirtualKeyCode::F11),
+            pixel_aspect: Some(VirtualKeyCode::F6),
+            overscan: Some(VirtualKeyCode::O),
+            integer_scaling: Some(VirtualKeyCode::F10),
+            screenshot: Some(VirtualKeyCode::F12),
+            record_video: Some(VirtualKeyCode::F4),
+            record_audio: Some(VirtualKeyCode::F1),
+            volume_up: Some(VirtualKeyCode::Equals),
+            volume_down: Some(VirtualKeyCode::Minus),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoConfig {
+    /// A built-in shader preset or a preset file.
+    pub shader: String,
+    /// Values for the shaders' `#parameter`s, applied to whichever preset is in use.
+    pub shader_parameters: BTreeMap<String, f32>,
+    pub overscan: Overscan,
+    pub pixel_aspect: PixelAspect,
+    pub integer_scaling: bool,
+    pub fullscreen: bool,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            shader: "nearest".to_string(),
+            shader_parameters: BTreeMap::new(),
+            overscan: Overscan::default(),
+            pixel_aspect: PixelAspect::default(),
+            integer_scaling: false,
+            fullscreen: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub driver: AudioDriver,
+    pub sample_rate: u32,
+    /// Milliseconds of audio kept queued ahead of the device.
+    pub latency: u32,
+    /// Master volume from 0 to 1.
+    pub volume: f32,
+    /// Gain, mute and solo for channels that differ from the default.
+    pub channels: BTreeMap<Channel, ChannelMix>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            driver: AudioDriver::default(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            latency: DEFAULT_LATENCY.as_millis() as u32,
+            volume: 1.0,
+            channels: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Times normal speed while fast-forward is held, or 0 for as fast as possible.
+    pub fast_forward_speed: u32,
+    pub hotkeys: Hotkeys,
+    pub video: VideoConfig,
+    pub audio: AudioConfig,
+    #[serde(rename = "player")]
+    pub players: Vec<PlayerBindings>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            fast_forward_speed: 4,
+            hotkeys: Hotkeys::default(),
+            video: VideoConfig::default(),
+            audio: AudioConfig::default(),
+            players: PlayerBindings::defaults(),
+        }
+    }
+}
+
+impl Config {
+    /// `config.toml` in the platform's config directory, e.g. `~/.config/rnes` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rnes").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, writing out the defaults first if it does not exist yet.
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            let config = Self::default();
+            config.save(path)?;
+            return Ok(config);
+        }
+
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+        }
+        fs::write(path, text).map_err(|e| format!("Unable to write {}: {e}", path.display()))
+    }
+}
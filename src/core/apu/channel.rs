@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Largest gain a single channel can be given.
+pub const MAX_CHANNEL_GAIN: f32 = 2.0;
+
+/// One of the APU's sound generators.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+pub const CHANNEL_COUNT: usize = 5;
+
+impl Channel {
+    pub const ALL: [Channel; CHANNEL_COUNT] = [
+        Channel::Pulse1,
+        Channel::Pulse2,
+        Channel::Triangle,
+        Channel::Noise,
+        Channel::Dmc,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Channel::ALL
+            .into_iter()
+            .find(|channel| channel.name() == name)
+            .ok_or_else(|| {
+                format!("Unknown channel '{name}', expected pulse1, pulse2, triangle, noise or dmc")
+            })
+    }
+}
+
+/// How loud a channel is in the mix. While any channel is soloed, only soloed channels that are
+/// not muted are heard.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelMix {
+    /// Scales the channel's output before mixing, from 0 to `MAX_CHANNEL_GAIN`.
+    pub gain: f32,
+    /// From -1 for only the left speaker to 1 for only the right.
+    pub pan: f32,
+    pub muted: bool,
+    pub solo: bool,
+}
+
+impl Default for ChannelMix {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            pan: 0.0,
+            muted: false,
+            solo: false,
+        }
+    }
+}
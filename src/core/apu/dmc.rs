@@ -1,16 +1,19 @@
-const RATE_TABLE: [u16; 16] = [
-    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 85, 72, 54,
-];
+use super::timer::Timer;
+use crate::core::{Savestate, StateReader, StateWriter};
 
+/// The delta modulation channel. It plays 1-bit delta samples read from PRG, each bit moving a
+/// 7-bit output level up or down by 2.
+///
+/// The DMC can't reach the bus itself, so `fetch_address` says when its sample buffer wants the
+/// next byte and whoever can read PRG passes it to `fill_sample_buffer`.
 #[derive(Default)]
 pub struct Dmc {
-    pub enabled: bool,
-
     pub interrupt_flag: bool,
+    pub irq_enabled: bool,
     pub loop_flag: bool,
 
-    pub rate: usize,
-
+    rate_index: u8,
+    timer: Timer,
     memory_reader: DmcMemoryReader,
     output_unit: DmcOutputUnit,
 }
@@ -20,7 +23,7 @@ struct DmcMemoryReader {
     pub sample_address: u16,
     pub sample_length: u16,
 
-    pub sample_buffer: u8,
+    pub sample_buffer: Option<u8>,
     pub current_address: u16,
     pub bytes_remaining: u16,
 }
@@ -34,39 +37,166 @@ struct DmcOutputUnit {
 }
 
 impl Dmc {
-    pub fn tick(&mut self) {
-        if self.output_unit.bits_remaining == 0 {
-            self.reset_output_unit();
+    /// Picks a rate from the region's table, see `Region::dmc_rates`.
+    pub fn set_rate(&mut self, index: u8, rates: &[u16; 16]) {
+        self.rate_index = index;
+        // The table counts CPU cycles per bit and the timer counts down to 0 from its period.
+        self.timer.set_period(rates[index as usize] - 1);
+    }
+
+    /// $4010: IRQ enable, loop and rate index.
+    pub fn write_control(&mut self, data: u8, rates: &[u16; 16]) {
+        self.irq_enabled = data & 0x80 > 0;
+        self.loop_flag = data & 0x40 > 0;
+        if !self.irq_enabled {
+            self.interrupt_flag = false;
         }
+        self.set_rate(data & 0xF, rates);
+    }
+
+    /// Keeps the rate index but takes its period from another region's table.
+    pub fn set_rates(&mut self, rates: &[u16; 16]) {
+        self.set_rate(self.rate_index, rates);
+    }
+
+    /// $4011: sets the output level directly.
+    pub fn direct_load(&mut self, data: u8) {
+        self.output_unit.output_level = data & 0x7F;
     }
 
-    fn reset_output_unit(&mut self) {
-        self.output_unit.bits_remaining = 8;
-        if self.memory_reader.bytes_remaining > 0 {
-            self.output_unit.silence_flag = false;
-            self.refill_output_unit();
-        } else {
-            self.output_unit.silence_flag = true;
-            self.reset_memory_reader();
+    /// $4012: samples start at $C000 plus a multiple of 64.
+    pub fn set_sample_address(&mut self, data: u8) {
+        self.memory_reader.sample_address = 0xC000 | (data as u16) << 6;
+    }
+
+    /// $4013: samples are a multiple of 16 bytes long, plus one.
+    pub fn set_sample_length(&mut self, data: u8) {
+        self.memory_reader.sample_length = (data as u16) << 4 | 1;
+    }
+
+    /// Bit 4 of $4015. Enabling starts the sample over only if it had finished.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.interrupt_flag = false;
+        if !enabled {
+            self.memory_reader.bytes_remaining = 0;
+        } else if self.memory_reader.bytes_remaining == 0 {
+            self.restart_sample();
         }
     }
 
-    fn refill_output_unit(&mut self) {
-        self.output_unit.shift_register = self.memory_reader.sample_buffer;
-        self.memory_reader.sample_buffer = 0;
-        self.memory_reader.bytes_remaining -= 1;
+    /// Whether there are sample bytes left to read, as bit 4 of $4015 reports.
+    pub fn active(&self) -> bool {
+        self.memory_reader.bytes_remaining > 0
     }
 
-    fn reset_memory_reader(&mut self) {
-        if self.loop_flag {
-            self.memory_reader.current_address = self.memory_reader.sample_address;
-            self.memory_reader.bytes_remaining = self.memory_reader.sample_length;
-        } else {
-            self.interrupt_flag = true;
+    /// Runs one CPU cycle.
+    pub fn tick(&mut self) {
+        if !self.timer.tick() {
+            return;
+        }
+
+        let output = &mut self.output_unit;
+        if !output.silence_flag {
+            if output.shift_register & 1 > 0 {
+                if output.output_level <= 125 {
+                    output.output_level += 2;
+                }
+            } else if output.output_level >= 2 {
+                output.output_level -= 2;
+            }
+        }
+        output.shift_register >>= 1;
+        output.bits_remaining = output.bits_remaining.saturating_sub(1);
+
+        if output.bits_remaining == 0 {
+            output.bits_remaining = 8;
+            match self.memory_reader.sample_buffer.take() {
+                Some(byte) => {
+                    output.silence_flag = false;
+                    output.shift_register = byte;
+                }
+                None => output.silence_flag = true,
+            }
+        }
+    }
+
+    /// The address of the next sample byte, while the sample buffer is empty and bytes remain.
+    pub fn fetch_address(&self) -> Option<u16> {
+        let reader = &self.memory_reader;
+        (reader.sample_buffer.is_none() && reader.bytes_remaining > 0)
+            .then_some(reader.current_address)
+    }
+
+    /// Takes the byte read from `fetch_address`.
+    pub fn fill_sample_buffer(&mut self, byte: u8) {
+        let reader = &mut self.memory_reader;
+        reader.sample_buffer = Some(byte);
+        reader.current_address = reader.current_address.checked_add(1).unwrap_or(0x8000);
+        reader.bytes_remaining -= 1;
+
+        if reader.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart_sample();
+            } else if self.irq_enabled {
+                self.interrupt_flag = true;
+            }
         }
     }
 
     pub fn get_sample(&self) -> f32 {
-        0.0
+        self.output_unit.output_level as f32
+    }
+
+    fn restart_sample(&mut self) {
+        self.memory_reader.current_address = self.memory_reader.sample_address;
+        self.memory_reader.bytes_remaining = self.memory_reader.sample_length;
+    }
+}
+
+impl Savestate for Dmc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.interrupt_flag);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.loop_flag);
+        state.write_u8(self.rate_index);
+        self.timer.save_state(state);
+
+        let reader = &self.memory_reader;
+        state.write_u16(reader.sample_address);
+        state.write_u16(reader.sample_length);
+        state.write_bool(reader.sample_buffer.is_some());
+        state.write_u8(reader.sample_buffer.unwrap_or(0));
+        state.write_u16(reader.current_address);
+        state.write_u16(reader.bytes_remaining);
+
+        let output = &self.output_unit;
+        state.write_u8(output.shift_register);
+        state.write_u8(output.bits_remaining);
+        state.write_bool(output.silence_flag);
+        state.write_u8(output.output_level);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.interrupt_flag = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.loop_flag = state.read_bool()?;
+        self.rate_index = state.read_u8()?;
+        self.timer.load_state(state)?;
+
+        let reader = &mut self.memory_reader;
+        reader.sample_address = state.read_u16()?;
+        reader.sample_length = state.read_u16()?;
+        let buffered = state.read_bool()?;
+        let byte = state.read_u8()?;
+        reader.sample_buffer = buffered.then_some(byte);
+        reader.current_address = state.read_u16()?;
+        reader.bytes_remaining = state.read_u16()?;
+
+        let output = &mut self.output_unit;
+        output.shift_register = state.read_u8()?;
+        output.bits_remaining = state.read_u8()?;
+        output.silence_flag = state.read_bool()?;
+        output.output_level = state.read_u8()?;
+        Ok(())
     }
 }
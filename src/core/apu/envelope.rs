@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 #[derive(Default)]
 pub struct Envelope {
     start: bool,
@@ -38,3 +40,24 @@ impl Envelope {
         }
     }
 }
+
+impl Savestate for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.start);
+        state.write_u8(self.divider);
+        state.write_u8(self.decay);
+        state.write_bool(self.should_loop);
+        state.write_bool(self.constant_volume);
+        state.write_u8(self.envelope);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.start = state.read_bool()?;
+        self.divider = state.read_u8()?;
+        self.decay = state.read_u8()?;
+        self.should_loop = state.read_bool()?;
+        self.constant_volume = state.read_bool()?;
+        self.envelope = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 #[derive(Default)]
 pub struct FrameCounter {
     counter: u32,
@@ -31,3 +33,16 @@ impl FrameCounter {
         self.counter != 4
     }
 }
+
+impl Savestate for FrameCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.counter);
+        state.write_bool(self.extended_step);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.counter = state.read_u32()?;
+        self.extended_step = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 const LENGTH_TABLE: [u8; 32] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
@@ -34,3 +36,18 @@ impl LengthCounter {
         }
     }
 }
+
+impl Savestate for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.counter);
+        state.write_bool(self.halt);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.enabled = state.read_bool()?;
+        self.counter = state.read_u8()?;
+        self.halt = state.read_bool()?;
+        Ok(())
+    }
+}
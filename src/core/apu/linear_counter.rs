@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 #[derive(Default)]
 pub struct LinearCounter {
     reload: bool,
@@ -31,3 +33,20 @@ impl LinearCounter {
         self.reload = true;
     }
 }
+
+impl Savestate for LinearCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.reload);
+        state.write_bool(self.control);
+        state.write_u8(self.reload_value);
+        state.write_u8(self.value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.reload = state.read_bool()?;
+        self.control = state.read_bool()?;
+        self.reload_value = state.read_u8()?;
+        self.value = state.read_u8()?;
+        Ok(())
+    }
+}
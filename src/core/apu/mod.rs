@@ -9,36 +9,141 @@ use triangle::Triangle;
 mod linear_counter;
 mod noise;
 use noise::Noise;
+mod dmc;
+use dmc::Dmc;
+mod channel;
 mod frame_counter;
 mod timer;
+pub use channel::*;
 
 use crate::{
     audio::AudioOutput,
-    core::{apu::frame_counter::FrameCounter, Addressable},
+    core::{
+        apu::frame_counter::FrameCounter, Addressable, Region, Savestate, StateReader, StateWriter,
+    },
 };
 
-const FRAME_COUNTER_FREQ: usize = 1789773 / 240;
-
 #[derive(Default)]
 pub struct APU {
     pulse: [Pulse; 2],
     triangle: Triangle,
     noise: Noise,
+    dmc: Dmc,
     cycle: usize,
     interrupt_inhibit: bool,
     audio_output: AudioOutput,
     frame_counter: FrameCounter,
     volume: f32,
+    muted: bool,
+    channel_mix: [ChannelMix; CHANNEL_COUNT],
+    // What each channel's output is scaled by, from `channel_mix`: unpanned, then in the left
+    // and right mixes.
+    channel_gains: [[f32; CHANNEL_COUNT]; 3],
+    region: Region,
+    // Every sample since the last `take_captured_samples`, while recording.
+    captured_samples: Option<CapturedSamples>,
+    capture_stems: bool,
+}
+
+/// Samples kept by `APU::set_sample_capture`, one per CPU cycle.
+#[derive(Default)]
+pub struct CapturedSamples {
+    /// The full mix with each channel's `ChannelMix` but no panning, before volume and muting.
+    pub mixed: Vec<f32>,
+    /// Each channel on its own through the same mixer, in `Channel::ALL` order and ignoring
+    /// `ChannelMix`. Empty unless stems were asked for.
+    pub stems: Vec<[f32; CHANNEL_COUNT]>,
 }
 
 impl APU {
     pub fn new(volume: f32) -> Self {
-        Self {
+        let mut apu = Self {
             volume,
+            channel_gains: [[1.0; CHANNEL_COUNT]; 3],
             ..Default::default()
+        };
+        apu.dmc.set_rates(apu.region.dmc_rates());
+        apu
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Scales everything played, from 0 to 1. Recordings are not affected.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn channel_mix(&self, channel: Channel) -> ChannelMix {
+        self.channel_mix[channel as usize]
+    }
+
+    pub fn set_channel_mix(&mut self, channel: Channel, mix: ChannelMix) {
+        self.channel_mix[channel as usize] = ChannelMix {
+            gain: mix.gain.clamp(0.0, MAX_CHANNEL_GAIN),
+            pan: mix.pan.clamp(-1.0, 1.0),
+            ..mix
+        };
+
+        let any_solo = self.channel_mix.iter().any(|mix| mix.solo);
+        for (index, mix) in self.channel_mix.iter().enumerate() {
+            let heard = !mix.muted && (mix.solo || !any_solo);
+            let gain = if heard { mix.gain } else { 0.0 };
+            self.channel_gains[0][index] = gain;
+            self.channel_gains[1][index] = gain * (1.0 - mix.pan).min(1.0);
+            self.channel_gains[2][index] = gain * (1.0 + mix.pan).min(1.0);
         }
     }
 
+    /// Replaces where the audio is played, e.g. with one from `audio::open_sink`.
+    pub fn set_audio_output(&mut self, audio_output: AudioOutput) {
+        self.audio_output = audio_output;
+    }
+
+    pub fn finish_audio(&mut self) -> Result<(), String> {
+        self.audio_output.finish()
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.dmc.set_rates(region.dmc_rates());
+        self.audio_output.set_clock_rate(region.cpu_clock());
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn set_audio_speed(&mut self, speed: Option<f32>) {
+        self.audio_output.set_speed(speed);
+    }
+
+    /// Starts or stops keeping a copy of every mixed sample, before volume and muting, and with
+    /// `stems` of every channel's output alone too.
+    pub fn set_sample_capture(&mut self, enabled: bool, stems: bool) {
+        self.captured_samples = enabled.then(CapturedSamples::default);
+        self.capture_stems = stems;
+    }
+
+    /// The samples mixed since the last call, one per CPU cycle.
+    pub fn take_captured_samples(&mut self) -> CapturedSamples {
+        self.captured_samples
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Where the DMC wants its next sample byte read from, if anywhere.
+    pub fn dmc_fetch_address(&self) -> Option<u16> {
+        self.dmc.fetch_address()
+    }
+
+    /// Hands the DMC the byte read from `dmc_fetch_address`.
+    pub fn fill_dmc(&mut self, byte: u8) {
+        self.dmc.fill_sample_buffer(byte);
+    }
+
     pub fn tick(&mut self, cycles: usize) {
         for _ in 0..cycles {
             self.cycle += 1;
@@ -48,30 +153,39 @@ impl APU {
                 self.noise.tick();
             }
             self.triangle.tick();
+            self.dmc.tick();
 
-            if self.cycle.is_multiple_of(FRAME_COUNTER_FREQ) {
+            let frame_counter_period = self.region.frame_counter_period();
+            if self.cycle.is_multiple_of(frame_counter_period) {
                 self.step_frame_counter();
             }
 
             let p0_sample = self.pulse[0].get_sample();
             let p1_sample = self.pulse[1].get_sample();
-            let pulse_sample = if p0_sample + p1_sample > 0.0 {
-                95.88 / ((8128.0 / (p0_sample + p1_sample)) + 100.0)
-            } else {
-                0.0
-            };
-
             let t_sample = self.triangle.get_sample();
             let n_sample = self.noise.get_sample();
-            let tn_mix = (t_sample / 8227.0) + (n_sample / 12241.0);
-            let tnd_sample = if tn_mix > 0.0 {
-                159.79 / ((1.0 / tn_mix) + 100.0)
-            } else {
-                0.0
-            };
-
-            self.audio_output
-                .push_sample((pulse_sample + tnd_sample) * self.volume);
+            let d_sample = self.dmc.get_sample();
+
+            let outputs = [p0_sample, p1_sample, t_sample, n_sample, d_sample];
+            let [_, left_gains, right_gains] = self.channel_gains;
+            let (left, right) = (mix(outputs, left_gains), mix(outputs, right_gains));
+            if let Some(captured_samples) = &mut self.captured_samples {
+                captured_samples
+                    .mixed
+                    .push(mix(outputs, self.channel_gains[0]));
+                if self.capture_stems {
+                    captured_samples.stems.push([
+                        mix_pulse(p0_sample),
+                        mix_pulse(p1_sample),
+                        mix_tnd(t_sample, 0.0, 0.0),
+                        mix_tnd(0.0, n_sample, 0.0),
+                        mix_tnd(0.0, 0.0, d_sample),
+                    ]);
+                }
+            }
+
+            let volume = if self.muted { 0.0 } else { self.volume };
+            self.audio_output.push_sample(left * volume, right * volume);
         }
     }
 
@@ -102,25 +216,33 @@ impl APU {
     }
 }
 
+// Channel outputs in `Channel::ALL` order, each scaled by its gain, through the console's mixer.
+fn mix(outputs: [f32; CHANNEL_COUNT], gains: [f32; CHANNEL_COUNT]) -> f32 {
+    let [p0, p1, t, n, d] = std::array::from_fn(|i| outputs[i] * gains[i]);
+    mix_pulse(p0 + p1) + mix_tnd(t, n, d)
+}
+
+// The console's nonlinear DAC for the two pulse channels, from their summed output.
+fn mix_pulse(pulse: f32) -> f32 {
+    if pulse > 0.0 {
+        95.88 / ((8128.0 / pulse) + 100.0)
+    } else {
+        0.0
+    }
+}
+
+fn mix_tnd(triangle: f32, noise: f32, dmc: f32) -> f32 {
+    let tnd_mix = (triangle / 8227.0) + (noise / 12241.0) + (dmc / 22638.0);
+    if tnd_mix > 0.0 {
+        159.79 / ((1.0 / tnd_mix) + 100.0)
+    } else {
+        0.0
+    }
+}
+
 impl Addressable for APU {
     fn read_byte(&mut self, address: u16) -> Option<u8> {
-        if address == 0x4015 {
-            let mut status = 0u8;
-            if !self.pulse[0].length_counter.mute() {
-                status |= 1;
-            }
-            if !self.pulse[1].length_counter.mute() {
-                status |= 2;
-            }
-            if !self.triangle.length_counter.mute() {
-                status |= 4;
-            }
-            if !self.noise.length_counter.mute() {
-                status |= 8;
-            }
-            return Some(status);
-        }
-        None
+        self.peek(address)
     }
 
     fn write_byte(&mut self, address: u16, data: u8) {
@@ -200,17 +322,23 @@ impl Addressable for APU {
                 self.noise.envelope.reload();
             }
             0x400E => {
-                self.noise.set_period(data & 0xF);
+                self.noise
+                    .set_period(data & 0xF, self.region.noise_periods());
             }
             0x400F => {
                 self.noise.length_counter.set_counter(data >> 3);
                 self.noise.envelope.reload();
             }
+            0x4010 => self.dmc.write_control(data, self.region.dmc_rates()),
+            0x4011 => self.dmc.direct_load(data),
+            0x4012 => self.dmc.set_sample_address(data),
+            0x4013 => self.dmc.set_sample_length(data),
             0x4015 => {
                 self.pulse[0].set_enabled(data & 1 > 0);
                 self.pulse[1].set_enabled(data & 2 > 0);
                 self.triangle.set_enabled(data & 4 > 0);
                 self.noise.set_enabled(data & 8 > 0);
+                self.dmc.set_enabled(data & 0x10 > 0);
             }
             0x4017 => {
                 self.frame_counter.set_mode(data & 0x80 > 0);
@@ -219,4 +347,62 @@ impl Addressable for APU {
             _ => {}
         }
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        if address == 0x4015 {
+            let mut status = 0u8;
+            if !self.pulse[0].length_counter.mute() {
+                status |= 1;
+            }
+            if !self.pulse[1].length_counter.mute() {
+                status |= 2;
+            }
+            if !self.triangle.length_counter.mute() {
+                status |= 4;
+            }
+            if !self.noise.length_counter.mute() {
+                status |= 8;
+            }
+            if self.dmc.active() {
+                status |= 0x10;
+            }
+            if self.dmc.interrupt_flag {
+                status |= 0x80;
+            }
+            return Some(status);
+        }
+        None
+    }
+
+    // Bit 5 of $4015 is not connected.
+    fn open_bus_mask(&self, _address: u16) -> u8 {
+        0x20
+    }
+}
+
+impl Savestate for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        for pulse in &self.pulse {
+            pulse.save_state(state);
+        }
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_usize(self.cycle);
+        state.write_bool(self.interrupt_inhibit);
+        self.frame_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        for pulse in &mut self.pulse {
+            pulse.load_state(state)?;
+        }
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.cycle = state.read_usize()?;
+        self.interrupt_inhibit = state.read_bool()?;
+        self.frame_counter.load_state(state)?;
+        Ok(())
+    }
 }
@@ -1,8 +1,5 @@
 use super::{envelope::Envelope, length_counter::LengthCounter, timer::Timer};
-
-const NOISE_PERIOD: [u16; 16] = [
-    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
-];
+use crate::core::{Savestate, StateReader, StateWriter};
 
 pub struct Noise {
     pub envelope: Envelope,
@@ -54,7 +51,27 @@ impl Noise {
         self.length_counter.set_enabled(enabled);
     }
 
-    pub fn set_period(&mut self, lut_index: u8) {
-        self.timer.set_period(NOISE_PERIOD[lut_index as usize]);
+    /// Picks a period from the region's table.
+    pub fn set_period(&mut self, lut_index: u8, periods: &[u16; 16]) {
+        self.timer.set_period(periods[lut_index as usize]);
+    }
+}
+
+impl Savestate for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.envelope.save_state(state);
+        self.length_counter.save_state(state);
+        state.write_bool(self.enabled);
+        self.timer.save_state(state);
+        state.write_u16(self.shift);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.envelope.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        self.timer.load_state(state)?;
+        self.shift = state.read_u16()?;
+        Ok(())
     }
 }
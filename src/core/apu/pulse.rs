@@ -1,4 +1,5 @@
 use super::{envelope::Envelope, length_counter::LengthCounter, sweep::Sweep, timer::Timer};
+use crate::core::{Savestate, StateReader, StateWriter};
 
 const DUTY_CYCLES: [[u32; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1],
@@ -42,3 +43,26 @@ impl Pulse {
         }
     }
 }
+
+impl Savestate for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        self.timer.save_state(state);
+        state.write_u8(self.duty_cycle);
+        state.write_usize(self.duty_timer);
+        self.sweep.save_state(state);
+        self.envelope.save_state(state);
+        self.length_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.enabled = state.read_bool()?;
+        self.timer.load_state(state)?;
+        self.duty_cycle = state.read_u8()?;
+        self.duty_timer = state.read_usize()?;
+        self.sweep.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        Ok(())
+    }
+}
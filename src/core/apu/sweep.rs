@@ -3,6 +3,8 @@ use std::cmp::max;
 use super::timer::Timer;
 use bitfield::bitfield;
 
+use crate::core::{Savestate, StateReader, StateWriter};
+
 bitfield! {
     #[derive(Copy, Clone, PartialEq)]
     pub struct SweepSetup(u8);
@@ -65,3 +67,22 @@ impl Sweep {
         self.muted
     }
 }
+
+impl Savestate for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.shift);
+        state.write_bool(self.negate);
+        state.write_bool(self.enable);
+        self.divider.save_state(state);
+        state.write_bool(self.muted);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.shift = state.read_u16()?;
+        self.negate = state.read_bool()?;
+        self.enable = state.read_bool()?;
+        self.divider.load_state(state)?;
+        self.muted = state.read_bool()?;
+        Ok(())
+    }
+}
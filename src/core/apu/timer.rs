@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 #[derive(Default)]
 pub struct Timer {
     current: u16,
@@ -29,3 +31,18 @@ impl Timer {
         self.reload
     }
 }
+
+impl Savestate for Timer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.current);
+        state.write_u16(self.reload);
+        state.write_bool(self.reload_now);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.current = state.read_u16()?;
+        self.reload = state.read_u16()?;
+        self.reload_now = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::{length_counter::LengthCounter, linear_counter::LinearCounter, timer::Timer};
+use crate::core::{Savestate, StateReader, StateWriter};
 
 const DUTY_TABLE: [u8; 32] = [
     15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
@@ -38,3 +39,22 @@ impl Triangle {
         DUTY_TABLE[self.duty_timer] as f32
     }
 }
+
+impl Savestate for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.duty_timer);
+        state.write_bool(self.enabled);
+        self.timer.save_state(state);
+        self.length_counter.save_state(state);
+        self.linear_counter.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.duty_timer = state.read_usize()?;
+        self.enabled = state.read_bool()?;
+        self.timer.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        self.linear_counter.load_state(state)?;
+        Ok(())
+    }
+}
@@ -3,9 +3,20 @@ use std::fmt::Display;
 use std::ops::RangeInclusive;
 use std::rc::Rc;
 
+use super::{Savestate, StateReader, StateWriter};
+
 pub trait Addressable {
     fn read_byte(&mut self, address: u16) -> Option<u8>;
     fn write_byte(&mut self, address: u16, data: u8);
+
+    /// Reads a byte without any of the side effects `read_byte` may have, such as clearing
+    /// PPUSTATUS or shifting the controller. Used by debuggers and other tooling.
+    fn peek(&self, address: u16) -> Option<u8>;
+
+    /// Bits of a read from `address` the component leaves undriven, which read back as open bus.
+    fn open_bus_mask(&self, _address: u16) -> u8 {
+        0
+    }
 }
 
 pub struct MemoryMapping {
@@ -15,7 +26,10 @@ pub struct MemoryMapping {
 
 pub struct Bus {
     regions: Vec<MemoryMapping>,
-    last_read: u8,
+    // The last value read or written. Every CPU cycle drives the data bus, so nothing has time to
+    // decay and undriven bits read back exactly this.
+    open_bus: u8,
+    write_log: Option<Vec<u16>>,
 }
 
 impl Display for Bus {
@@ -34,7 +48,8 @@ impl Bus {
     pub fn new() -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Self {
             regions: Vec::new(),
-            last_read: 0,
+            open_bus: 0,
+            write_log: None,
         }))
     }
 
@@ -47,17 +62,38 @@ impl Bus {
     }
 
     pub fn read_byte(&mut self, address: u16) -> u8 {
-        for mapping in &self.regions {
-            if mapping.region.contains(&address) {
-                return mapping
-                    .component
-                    .borrow_mut()
-                    .read_byte(address)
-                    .unwrap_or(self.last_read);
+        let data = match self.mapping(address) {
+            Some(mapping) => {
+                let mut component = mapping.component.borrow_mut();
+                let mask = component.open_bus_mask(address);
+                component.read_byte(address).map_or(self.open_bus, |data| {
+                    (data & !mask) | (self.open_bus & mask)
+                })
             }
+            None => self.open_bus,
+        };
+        self.open_bus = data;
+        data
+    }
+
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        match self.mapping(address) {
+            Some(mapping) => {
+                let component = mapping.component.borrow();
+                let mask = component.open_bus_mask(address);
+                component.peek(address).map_or(self.open_bus, |data| {
+                    (data & !mask) | (self.open_bus & mask)
+                })
+            }
+            None => self.open_bus,
         }
+    }
 
-        self.last_read
+    // Reads come from the first component mapped at an address.
+    fn mapping(&self, address: u16) -> Option<&MemoryMapping> {
+        self.regions
+            .iter()
+            .find(|mapping| mapping.region.contains(&address))
     }
 
     pub fn read_word(&mut self, address: u16) -> u16 {
@@ -73,6 +109,11 @@ impl Bus {
     }
 
     pub fn write_byte(&mut self, address: u16, data: u8) {
+        if let Some(write_log) = &mut self.write_log {
+            write_log.push(address);
+        }
+        self.open_bus = data;
+
         for mapping in &self.regions {
             if mapping.region.contains(&address) {
                 mapping.component.borrow_mut().write_byte(address, data);
@@ -84,4 +125,28 @@ impl Bus {
         self.write_byte(address, data as u8);
         self.write_byte(address + 1, (data >> 8) as u8);
     }
+
+    /// Records the address of every write until disabled. Used by debugging tools.
+    pub fn set_write_tracking(&mut self, enabled: bool) {
+        self.write_log = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn take_writes(&mut self) -> Vec<u16> {
+        match &mut self.write_log {
+            Some(write_log) => std::mem::take(write_log),
+            None => Vec::new(),
+        }
+    }
+}
+
+// The components mapped onto the bus save themselves; the bus only keeps the open bus value.
+impl Savestate for Bus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.open_bus);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.open_bus = state.read_u8()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,81 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::rom::Cartridge;
+
+pub const PRG_CODE: u8 = 0x01;
+pub const PRG_DATA: u8 = 0x02;
+pub const PRG_INDIRECT_CODE: u8 = 0x10;
+pub const PRG_INDIRECT_DATA: u8 = 0x20;
+pub const PRG_PCM_DATA: u8 = 0x40;
+const PRG_BANK_MASK: u8 = 0x0C;
+
+pub const CHR_RENDERED: u8 = 0x01;
+pub const CHR_READ: u8 = 0x02;
+
+/// Records how each PRG and CHR ROM byte was used, in the FCEUX `.cdl` layout.
+///
+/// The file is one flag byte per PRG ROM byte followed by one per CHR ROM byte. PRG bytes also
+/// store bits 12-13 of the CPU address they were last accessed through, which tells
+/// disassemblers which 8KB window the bank was mapped into.
+pub struct CodeDataLogger {
+    cartridge: Rc<RefCell<dyn Cartridge>>,
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl CodeDataLogger {
+    pub fn new(cartridge: Rc<RefCell<dyn Cartridge>>) -> Self {
+        let (prg_size, chr_size) = {
+            let cartridge = cartridge.borrow();
+            (cartridge.prg_rom_size(), cartridge.chr_rom_size())
+        };
+
+        Self {
+            cartridge,
+            prg: vec![0; prg_size],
+            chr: vec![0; chr_size],
+        }
+    }
+
+    /// Merges a previously saved log so coverage accumulates across sessions.
+    pub fn merge(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() != self.prg.len() + self.chr.len() {
+            return Err(format!(
+                "CDL file is {} bytes but this ROM needs {}",
+                data.len(),
+                self.prg.len() + self.chr.len()
+            ));
+        }
+
+        let (prg, chr) = data.split_at(self.prg.len());
+        for (flags, &saved) in self.prg.iter_mut().zip(prg) {
+            *flags |= saved;
+        }
+        for (flags, &saved) in self.chr.iter_mut().zip(chr) {
+            *flags |= saved;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.prg.as_slice(), self.chr.as_slice()].concat()
+    }
+
+    /// Marks the PRG ROM byte mapped at a CPU address. Addresses outside PRG ROM are ignored.
+    pub fn log_prg(&mut self, address: u16, flags: u8) {
+        let Some(offset) = self.cartridge.borrow().prg_rom_offset(address) else {
+            return;
+        };
+        let bank = ((address >> 13) as u8 & 0b11) << 2;
+        let entry = &mut self.prg[offset];
+        *entry = (*entry & !PRG_BANK_MASK) | bank | flags;
+    }
+
+    /// Marks the CHR ROM byte mapped at a PPU address. CHR RAM is ignored.
+    pub fn log_chr(&mut self, address: u16, flags: u8) {
+        if let Some(offset) = self.cartridge.borrow().chr_rom_offset(address) {
+            self.chr[offset] |= flags;
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{ControllerPorts, PpuView, Region, Savestate, StateReader, StateWriter, APU, PPU};
+use crate::{rom::Cartridge, window::NATIVE_RESOLUTION};
+
+/// Everything that runs alongside the CPU. The CPU steps it one cycle before each of its bus
+/// accesses, so reads and writes see the PPU and APU exactly where they would be on the console.
+pub struct SystemClock {
+    ppu: Rc<RefCell<PPU>>,
+    apu: Rc<RefCell<APU>>,
+    controllers: Rc<RefCell<ControllerPorts>>,
+    cartridge: Rc<RefCell<dyn Cartridge>>,
+    region: Region,
+    // PPU dots owed to the PPU from CPU cycles that did not divide evenly, on PAL.
+    ppu_dot_remainder: usize,
+    screen: Vec<u32>,
+    pixels: Vec<u16>,
+    // Dots run so far modulo 3, and the same at the first visible dot of the latest frame. A dot
+    // is 8 master clocks and a cycle of the color subcarrier 12, so its phase repeats every 3.
+    dot_phase: usize,
+    frame_phase: usize,
+    frame_complete: bool,
+    ppu_view_request: Option<(u32, u8)>,
+    ppu_view: Option<PpuView>,
+}
+
+impl SystemClock {
+    pub fn new(
+        ppu: Rc<RefCell<PPU>>,
+        apu: Rc<RefCell<APU>>,
+        controllers: Rc<RefCell<ControllerPorts>>,
+        cartridge: Rc<RefCell<dyn Cartridge>>,
+    ) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            ppu,
+            apu,
+            controllers,
+            cartridge,
+            region: Region::default(),
+            ppu_dot_remainder: 0,
+            screen: vec![0; NATIVE_RESOLUTION.width as usize * NATIVE_RESOLUTION.height as usize],
+            pixels: vec![0; NATIVE_RESOLUTION.width as usize * NATIVE_RESOLUTION.height as usize],
+            dot_phase: 0,
+            frame_phase: 0,
+            frame_complete: false,
+            ppu_view_request: None,
+            ppu_view: None,
+        }))
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    /// Runs the PPU and APU for one CPU cycle. Returns true if the PPU raised an NMI.
+    pub fn cpu_cycle(&mut self) -> bool {
+        self.cartridge.borrow_mut().cpu_cycle();
+
+        let (dots_numerator, dots_denominator) = self.region.ppu_dots_per_cpu_cycle();
+        let dots = dots_numerator + self.ppu_dot_remainder;
+        self.ppu_dot_remainder = dots % dots_denominator;
+
+        let mut nmi = false;
+        let mut ppu = self.ppu.borrow_mut();
+        for _ in 0..(dots / dots_denominator) {
+            nmi |= ppu.tick(&mut self.screen, &mut self.pixels);
+            self.dot_phase = (self.dot_phase + 1) % 3;
+            // Dot 1 of scanline 0 was just drawn. Finding it this way takes care of the dot
+            // skipped before it on odd frames.
+            if ppu.scanline() == 0 && ppu.cycle() == 2 {
+                self.frame_phase = (self.dot_phase + 2) % 3;
+            }
+            self.frame_complete |= ppu.take_frame_complete();
+            if let Some((scanline, pattern_palette)) = self.ppu_view_request {
+                if ppu.scanline() == scanline && ppu.cycle() == 0 {
+                    self.ppu_view = Some(ppu.capture_view(pattern_palette));
+                    self.ppu_view_request = None;
+                }
+            }
+        }
+        self.controllers
+            .borrow_mut()
+            .sense_light(&self.screen, ppu.scanline(), ppu.cycle());
+        self.apu.borrow_mut().tick(1);
+
+        nmi
+    }
+
+    /// Where the DMC wants its next sample byte read from. The CPU fetches it with a DMA.
+    pub fn dmc_fetch_address(&self) -> Option<u16> {
+        self.apu.borrow().dmc_fetch_address()
+    }
+
+    pub fn fill_dmc(&mut self, byte: u8) {
+        self.apu.borrow_mut().fill_dmc(byte);
+    }
+
+    /// Whether the PPU has entered vblank since the previous call.
+    pub fn take_frame_complete(&mut self) -> bool {
+        std::mem::take(&mut self.frame_complete)
+    }
+
+    pub fn screen(&self) -> &[u32] {
+        &self.screen
+    }
+
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    /// The color subcarrier phase, from 0 to 11, at the first visible dot of the frame.
+    pub fn color_phase(&self) -> usize {
+        self.frame_phase * 8 % 12
+    }
+
+    pub fn request_ppu_view(&mut self, scanline: u32, pattern_palette: u8) {
+        self.ppu_view_request = Some((scanline, pattern_palette));
+    }
+
+    pub fn take_ppu_view(&mut self) -> Option<PpuView> {
+        self.ppu_view.take()
+    }
+}
+
+impl Savestate for SystemClock {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.ppu_dot_remainder);
+        state.write_usize(self.dot_phase);
+        state.write_usize(self.frame_phase);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.ppu_dot_remainder = state.read_usize()?;
+        self.dot_phase = state.read_usize()? % 3;
+        self.frame_phase = state.read_usize()? % 3;
+        self.frame_complete = false;
+        Ok(())
+    }
+}
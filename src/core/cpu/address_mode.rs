@@ -318,31 +318,6 @@ impl AddressMode {
         OPCODE_ADDRESS_MODES[opcode as usize]
     }
 
-    pub fn cycle_cost(&self, page_cross: bool) -> usize {
-        match &self {
-            AddressMode::Implied => 0,
-            AddressMode::Accumulator | AddressMode::Immediate => 1,
-            AddressMode::ZeroPage => 2,
-            AddressMode::ZeroPageX | AddressMode::ZeroPageY | AddressMode::Absolute => 3,
-            AddressMode::AbsoluteX | AddressMode::AbsoluteY => {
-                if page_cross {
-                    4
-                } else {
-                    3
-                }
-            }
-            AddressMode::IndirectX => 5,
-            AddressMode::IndirectY => {
-                if page_cross {
-                    5
-                } else {
-                    4
-                }
-            }
-            AddressMode::Indirect => 5,
-        }
-    }
-
     pub fn byte_code_size(&self) -> u16 {
         match &self {
             AddressMode::Implied | AddressMode::Accumulator => 0,
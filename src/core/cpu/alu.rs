@@ -29,35 +29,29 @@ impl OpcodeGroup {
 }
 
 impl CPU {
-    pub fn run_alu_op(&mut self, opcode: u8) -> usize {
+    pub fn run_alu_op(&mut self, opcode: u8) {
         let address_mode = AddressMode::from_code(opcode);
         let opcode_group = OpcodeGroup::from_code(opcode >> 5);
-        let (operand, page_cross) = if opcode_group != OpcodeGroup::Sta {
-            self.read_operand(address_mode)
-        } else {
-            (self.a, true)
-        };
+        if opcode_group == OpcodeGroup::Sta {
+            if opcode == 0x89 {
+                self.nop(address_mode);
+            } else {
+                self.write_operand(self.a, address_mode);
+            }
+            return;
+        }
 
+        let operand = self.read_operand(address_mode);
         match opcode_group {
             OpcodeGroup::Ora => self.ora(operand),
             OpcodeGroup::And => self.and(operand),
             OpcodeGroup::Eor => self.eor(operand),
             OpcodeGroup::Adc => self.adc(operand),
-            OpcodeGroup::Sta => {
-                if opcode == 0x89 {
-                    self.nop(address_mode);
-                } else {
-                    self.write_operand(self.a, address_mode);
-                }
-            }
+            OpcodeGroup::Sta => unreachable!(),
             OpcodeGroup::Lda => self.lda(operand),
             OpcodeGroup::Cmp => self.cmp(operand),
             OpcodeGroup::Sbc => self.sbc(operand),
         }
-
-        self.pc += 1 + address_mode.byte_code_size();
-
-        1 + address_mode.cycle_cost(page_cross)
     }
 
     pub(super) fn ora(&mut self, operand: u8) {
@@ -1,74 +1,53 @@
-use super::{AddressMode, CoreError, StatusRegister, CPU};
+use super::{AddressMode, CoreError, StatusRegister, CPU, PRG_DATA, PRG_INDIRECT_CODE};
 
 impl CPU {
-    pub fn run_control_op(&mut self, opcode: u8) -> Result<usize, CoreError> {
+    pub fn run_control_op(&mut self, opcode: u8) -> Result<(), CoreError> {
         let address_mode = AddressMode::from_code(opcode);
-        let (operand, page_cross) = match opcode {
-            0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xB0 | 0xD0 | 0xF0 | 0x24 | 0x2C | 0xA0 | 0xA4
-            | 0xB4 | 0xAC | 0xBC | 0xC0 | 0xC4 | 0xCC | 0xE0 | 0xE4 | 0xEC => {
-                self.read_operand(address_mode)
-            }
-            _ => (0, false),
-        };
-
-        let mut use_page_cross = false;
-        let cycles = match opcode {
+        match opcode {
             0 => self.brk(),
             0x8 => self.php(),
             0x28 => self.plp(),
             0x48 => self.pha(),
             0x68 => self.pla(),
-            0x10 => self.branch(operand, !self.p.n()),
-            0x30 => self.branch(operand, self.p.n()),
-            0x50 => self.branch(operand, !self.p.v()),
-            0x70 => self.branch(operand, self.p.v()),
-            0x90 => self.branch(operand, !self.p.c()),
-            0xB0 => self.branch(operand, self.p.c()),
-            0xD0 => self.branch(operand, !self.p.z()),
-            0xF0 => self.branch(operand, self.p.z()),
-            0x18 => {
-                self.p.set_c(false);
-                2
-            }
-            0x38 => {
-                self.p.set_c(true);
-                2
-            }
-            0x58 => {
-                self.p.set_i(false);
-                2
-            }
-            0x78 => {
-                self.p.set_i(true);
-                2
-            }
-            0xB8 => {
-                self.p.set_v(false);
-                2
-            }
-            0xD8 => {
-                self.p.set_d(false);
-                2
-            }
-            0xF8 => {
-                self.p.set_d(true);
-                2
-            }
+            0x10 => self.branch(!self.p.n()),
+            0x30 => self.branch(self.p.n()),
+            0x50 => self.branch(!self.p.v()),
+            0x70 => self.branch(self.p.v()),
+            0x90 => self.branch(!self.p.c()),
+            0xB0 => self.branch(self.p.c()),
+            0xD0 => self.branch(!self.p.z()),
+            0xF0 => self.branch(self.p.z()),
+            0x18 => self.p.set_c(false),
+            0x38 => self.p.set_c(true),
+            0x58 => self.p.set_i(false),
+            0x78 => self.p.set_i(true),
+            0xB8 => self.p.set_v(false),
+            0xD8 => self.p.set_d(false),
+            0xF8 => self.p.set_d(true),
             0x20 => self.jsr(),
-            0x24 | 0x2C => self.bit(operand),
+            0x24 | 0x2C => {
+                let operand = self.read_operand(address_mode);
+                self.bit(operand)
+            }
             0x40 => self.rti(),
             0x60 => self.rts(),
             0x4C | 0x6C => self.jmp(address_mode),
-            0x84 | 0x94 | 0x8C => self.sty(address_mode),
+            0x84 | 0x94 | 0x8C => self.write_operand(self.y, address_mode),
             0x88 => self.dey(),
             0x98 => self.tya(),
             0xA8 => self.tay(),
             0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => {
-                use_page_cross = true;
+                let operand = self.read_operand(address_mode);
                 self.ldy(operand)
             }
-            0xC0 | 0xC4 | 0xCC => self.cpy(operand),
-            0xE0 | 0xE4 | 0xEC => self.cpx(operand),
+            0xC0 | 0xC4 | 0xCC => {
+                let operand = self.read_operand(address_mode);
+                self.cpy(operand)
+            }
+            0xE0 | 0xE4 | 0xEC => {
+                let operand = self.read_operand(address_mode);
+                self.cpx(operand)
+            }
             0xC8 => self.iny(),
             0xE8 => self.inx(),
             0x04 | 0x0C | 0x14 | 0x1C | 0x34 | 0x3C | 0x44 | 0x54 | 0x5C | 0x64 | 0x74 | 0x7C
@@ -77,150 +56,141 @@ impl CPU {
             _ => return Err(CoreError::OpcodeNotImplemented(opcode)),
         };
 
-        match opcode {
-            0x00 | 0x20 | 0x4C | 0x6C | 0x40 => {}
-            _ => self.pc += 1 + address_mode.byte_code_size(),
-        }
-
-        Ok(cycles + address_mode.cycle_cost(use_page_cross && page_cross))
+        Ok(())
     }
 
-    fn brk(&mut self) -> usize {
-        self.push_word(self.pc + 2);
+    fn brk(&mut self) {
+        // The byte after BRK is skipped, so RTI returns past it.
+        self.pc = self.pc.wrapping_add(1);
+        self.push_word(self.pc);
         self.php();
-        self.pc = self.bus.borrow_mut().read_word(0xFFFEu16);
+        self.pc = self.read_vector(0xFFFE);
         self.p.set_i(true);
-        7
     }
 
-    fn php(&mut self) -> usize {
+    fn php(&mut self) {
         let mut status = self.p;
         status.set_b(3u8);
         self.push_byte(status.0);
-        3
     }
 
-    fn plp(&mut self) -> usize {
+    fn plp(&mut self) {
+        self.read_stack();
         let mut status = StatusRegister(self.pop_byte());
         status.set_b(0u8);
         self.p = status;
-        4
     }
 
-    fn pha(&mut self) -> usize {
+    fn pha(&mut self) {
         self.push_byte(self.a);
-        3
     }
 
-    fn pla(&mut self) -> usize {
+    fn pla(&mut self) {
+        self.read_stack();
         self.a = self.pop_byte();
         self.set_nz_flags(self.a);
-        4
     }
 
-    fn branch(&mut self, operand: u8, should_branch: bool) -> usize {
+    // A taken branch reads the next opcode while it adds the offset, then again from the wrong
+    // page if it has to fix up the high byte.
+    fn branch(&mut self, should_branch: bool) {
+        let offset = self.fetch() as i8;
         if !should_branch {
-            return 1;
+            return;
         }
 
-        let offset = operand as u16;
-        let prev_pc = self.pc + 1;
-        self.pc = if offset >= 0x80 {
-            self.pc.wrapping_add(offset | 0xFF00)
-        } else {
-            self.pc.wrapping_add(offset)
-        };
-
-        if (prev_pc & 0xFF00) != (self.pc & 0xFF00) {
-            3
-        } else {
-            2
+        self.read(self.pc);
+        let target = self.pc.wrapping_add_signed(offset as i16);
+        if (self.pc & 0xFF00) != (target & 0xFF00) {
+            self.read((self.pc & 0xFF00) | (target & 0xFF));
         }
+        self.pc = target;
     }
 
-    fn jsr(&mut self) -> usize {
-        // PC + 3'size of ins' - 1'RTS has size 1'
-        self.push_word(self.pc + 2);
-        self.pc = self.bus.borrow_mut().read_word(self.pc + 1);
-        3
+    // The return address is pushed between fetching the two bytes of the target, so it points
+    // at the last byte of the JSR.
+    fn jsr(&mut self) {
+        let low = self.fetch() as u16;
+        self.read_stack();
+        self.push_word(self.pc);
+        let high = self.read(self.pc) as u16;
+        self.pc = (high << 8) | low;
     }
 
-    fn bit(&mut self, operand: u8) -> usize {
+    fn bit(&mut self, operand: u8) {
         self.p.set_z(self.a & operand == 0);
         self.p.set_v(operand & 0x40 > 0);
         self.p.set_n(operand & 0x80 > 0);
-        1
     }
 
-    fn rti(&mut self) -> usize {
+    fn rti(&mut self) {
+        self.read_stack();
         self.p.0 = self.pop_byte();
         self.p.set_b(0);
         self.pc = self.pop_word();
-        6
     }
 
-    fn rts(&mut self) -> usize {
+    fn rts(&mut self) {
+        self.read_stack();
         self.pc = self.pop_word();
-        6
-    }
-
-    fn jmp(&mut self, address_mode: AddressMode) -> usize {
-        self.pc = self.get_address(address_mode).0;
-        0
-    }
-
-    fn sty(&mut self, address_mode: AddressMode) -> usize {
-        self.write_operand(self.y, address_mode);
-        1
+        self.fetch();
+    }
+
+    fn jmp(&mut self, address_mode: AddressMode) {
+        let address = self.operand_address(AddressMode::Absolute, false);
+        if address_mode == AddressMode::Indirect {
+            // The pointer's high byte comes from the start of the same page if it would cross.
+            let high_address = (address & 0xFF00) | (address.wrapping_add(1) & 0xFF);
+            let low = self.read(address) as u16;
+            let high = self.read(high_address) as u16;
+            self.log_prg(address, PRG_DATA);
+            self.log_prg(high_address, PRG_DATA);
+            self.pc = (high << 8) | low;
+            self.log_prg(self.pc, PRG_INDIRECT_CODE);
+        } else {
+            self.pc = address;
+        }
     }
 
-    fn dey(&mut self) -> usize {
+    fn dey(&mut self) {
         self.y = self.y.wrapping_sub(1);
         self.set_nz_flags(self.y);
-        2
     }
 
-    fn tya(&mut self) -> usize {
+    fn tya(&mut self) {
         self.a = self.y;
         self.set_nz_flags(self.a);
-        2
     }
 
-    fn tay(&mut self) -> usize {
+    fn tay(&mut self) {
         self.y = self.a;
         self.set_nz_flags(self.y);
-        2
     }
 
-    fn ldy(&mut self, operand: u8) -> usize {
+    fn ldy(&mut self, operand: u8) {
         self.y = operand;
         self.set_nz_flags(self.y);
-        1
     }
 
-    fn cpy(&mut self, operand: u8) -> usize {
+    fn cpy(&mut self, operand: u8) {
         let result = self.y.wrapping_sub(operand);
         self.p.set_c(self.y >= operand);
         self.set_nz_flags(result);
-        1
     }
 
-    fn cpx(&mut self, operand: u8) -> usize {
+    fn cpx(&mut self, operand: u8) {
         let result = self.x.wrapping_sub(operand);
         self.p.set_c(self.x >= operand);
         self.set_nz_flags(result);
-        1
     }
 
-    fn iny(&mut self) -> usize {
+    fn iny(&mut self) {
         self.y = self.y.wrapping_add(1);
         self.set_nz_flags(self.y);
-        2
     }
 
-    fn inx(&mut self) -> usize {
+    fn inx(&mut self) {
         self.x = self.x.wrapping_add(1);
         self.set_nz_flags(self.x);
-        2
     }
 }
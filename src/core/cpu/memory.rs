@@ -1,4 +1,4 @@
-use super::Addressable;
+use super::{Addressable, Savestate, StateReader, StateWriter};
 use std::{cell::RefCell, rc::Rc};
 
 const CPU_INTERNAL_RAM_SIZE: usize = 0x800;
@@ -17,10 +17,24 @@ impl InternalRam {
 
 impl Addressable for InternalRam {
     fn read_byte(&mut self, address: u16) -> Option<u8> {
-        Some(self.data[(address % CPU_INTERNAL_RAM_SIZE as u16) as usize])
+        self.peek(address)
     }
 
     fn write_byte(&mut self, address: u16, data: u8) {
         self.data[(address % CPU_INTERNAL_RAM_SIZE as u16) as usize] = data
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        Some(self.data[(address % CPU_INTERNAL_RAM_SIZE as u16) as usize])
+    }
+}
+
+impl Savestate for InternalRam {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.data)
+    }
 }
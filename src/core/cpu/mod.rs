@@ -11,7 +11,10 @@ mod unofficial;
 use crate::core::cpu::{address_mode::AddressMode, opcodes::OPCODES};
 
 use self::{memory::InternalRam, status::StatusRegister};
-use super::{Addressable, Bus, CoreError};
+use super::{
+    Addressable, Bus, CodeDataLogger, CoreError, Savestate, StateReader, StateWriter, SystemClock,
+    PRG_CODE, PRG_DATA, PRG_INDIRECT_CODE, PRG_INDIRECT_DATA, PRG_PCM_DATA,
+};
 use std::{cell::RefCell, rc::Rc};
 
 #[derive(Copy, Clone, Debug)]
@@ -26,7 +29,6 @@ const OAM_DMA_SIZE: usize = 256;
 
 #[derive(Copy, Clone, Default)]
 pub struct OamDmaRequest {
-    started: bool,
     address: u16,
     length: usize,
 }
@@ -39,17 +41,24 @@ impl Addressable for OamDmaRequest {
 
     fn write_byte(&mut self, address: u16, data: u8) {
         if address == 0x4014 {
-            self.started = false;
             self.address = (data as u16) << 8;
             self.length = OAM_DMA_SIZE
         } else {
             println!("(warn) Unexpected write to {address:X} in OAM CPU register");
         }
     }
+
+    fn peek(&self, _address: u16) -> Option<u8> {
+        None
+    }
 }
 
+/// A 6502 that accesses the bus once per cycle like the real chip, dummy accesses included, with
+/// the rest of the console clocked before each one.
 pub struct CPU {
     bus: Rc<RefCell<Bus>>,
+    clock: Rc<RefCell<SystemClock>>,
+    ram: Rc<RefCell<InternalRam>>,
     pub a: u8,
     x: u8,
     y: u8,
@@ -57,21 +66,30 @@ pub struct CPU {
     pc: u16,
     pub p: StatusRegister,
     interrupt: Option<Interrupt>,
+    // Set when the PPU raises an NMI, until the CPU starts handling it.
+    nmi_pending: bool,
+    // Whether an NMI was pending going into the current cycle. Interrupts are checked before the
+    // last cycle of an instruction, so this is what counts once it finishes.
+    nmi_polled: bool,
     show_ops: bool,
     oam_request: Rc<RefCell<OamDmaRequest>>,
     cycles: usize,
+    code_data_logger: Option<Rc<RefCell<CodeDataLogger>>>,
 }
 
 impl CPU {
-    pub fn new(bus: &Rc<RefCell<Bus>>) -> Self {
+    pub fn new(bus: &Rc<RefCell<Bus>>, clock: &Rc<RefCell<SystemClock>>) -> Self {
+        let ram = InternalRam::new();
         bus.borrow_mut()
-            .register_region(0x0u16..=0x1FFFu16, InternalRam::new());
+            .register_region(0x0u16..=0x1FFFu16, ram.clone());
         let oam_request = Rc::new(RefCell::new(OamDmaRequest::default()));
         bus.borrow_mut()
             .register_region(0x4014u16..=0x4014u16, oam_request.clone());
 
         Self {
             bus: bus.clone(),
+            clock: clock.clone(),
+            ram,
             a: 0,
             x: 0,
             y: 0,
@@ -79,9 +97,12 @@ impl CPU {
             pc: 0xFFFCu16,
             p: StatusRegister(0),
             interrupt: Some(Interrupt::Reset),
+            nmi_pending: false,
+            nmi_polled: false,
             show_ops: false,
             oam_request,
             cycles: 0,
+            code_data_logger: None,
         }
     }
 
@@ -89,65 +110,65 @@ impl CPU {
         self.show_ops = show;
     }
 
-    pub fn generate_nmi(&mut self) {
-        self.interrupt = Some(Interrupt::Nmi);
+    pub fn set_code_data_logger(&mut self, logger: Option<Rc<RefCell<CodeDataLogger>>>) {
+        self.code_data_logger = logger;
+    }
+
+    /// Jumps through the reset vector before the next instruction, like the console's reset button.
+    pub fn reset(&mut self) {
+        self.interrupt = Some(Interrupt::Reset);
     }
 
+    /// Runs an OAM DMA or the next instruction and returns how many cycles it took.
     pub fn tick(&mut self) -> Result<usize, CoreError> {
-        let oam_request_length = self.oam_request.borrow().length;
-        if oam_request_length > 0 {
-            let mut request = self.oam_request.borrow_mut();
-            if !request.started {
-                request.started = true;
-                let extra_cycle = if self.cycles.is_multiple_of(2) { 0 } else { 1 };
-                return Ok(1 + extra_cycle);
+        let start_cycle = self.cycles;
+
+        if self.oam_request.borrow().length > 0 {
+            self.run_oam_dma();
+        } else {
+            if let Some(interrupt) = self.interrupt.take() {
+                self.run_interrupt(interrupt);
             }
-            drop(request);
-
-            let mut bus = self.bus.borrow_mut();
-            let oam_address = {
-                let mut request = self.oam_request.borrow_mut();
-                let address = request.address;
-                request.address += 1;
-                request.length -= 1;
-                address
-            };
-            let oam_byte = bus.read_byte(oam_address);
-            bus.write_byte(0x2004, oam_byte);
-            return Ok(2);
+            self.run_instruction()?;
         }
 
-        if let Some(interrupt) = self.interrupt {
-            self.push_word(self.pc);
-            let mut status = self.p;
-            status.set_b(0);
-            self.push_byte(status.0);
-            let vector_address = match interrupt {
-                Interrupt::Nmi => 0xFFFA,
-                Interrupt::Reset => 0xFFFC,
-            };
-            self.pc = self.bus.borrow_mut().read_word(vector_address);
-            self.p.set_i(true);
-            self.interrupt = None;
+        if self.nmi_polled && self.interrupt.is_none() {
+            self.nmi_pending = false;
+            self.interrupt = Some(Interrupt::Nmi);
         }
 
-        let opcode = self.bus.borrow_mut().read_byte(self.pc);
+        Ok(self.cycles - start_cycle)
+    }
+
+    fn run_instruction(&mut self) -> Result<(), CoreError> {
+        let opcode_address = self.pc;
+        let opcode = self.fetch();
+        let address_mode = AddressMode::from_code(opcode);
+        for offset in 0..=address_mode.byte_code_size() {
+            self.log_prg(opcode_address.wrapping_add(offset), PRG_CODE);
+        }
         if self.show_ops {
             print!(
                 "c{} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{} ${:04X}: ({:02X}) {} {}",
-                self.cycles,
+                self.cycles - 1,
                 self.a,
                 self.x,
                 self.y,
                 self.sp,
                 self.p,
-                self.pc,
+                opcode_address,
                 opcode,
                 OPCODES[opcode as usize],
-                AddressMode::from_code(opcode)
+                address_mode
             );
         }
-        let cycles = match opcode % 4 {
+
+        // One byte instructions still read the next byte on their second cycle.
+        if let AddressMode::Implied | AddressMode::Accumulator = address_mode {
+            self.read(self.pc);
+        }
+
+        match opcode % 4 {
             0 => self.run_control_op(opcode)?,
             1 => self.run_alu_op(opcode),
             2 => self.run_rmw_op(opcode)?,
@@ -159,9 +180,105 @@ impl CPU {
             println!();
         }
 
-        self.cycles += cycles;
+        Ok(())
+    }
+
+    fn run_interrupt(&mut self, interrupt: Interrupt) {
+        self.read(self.pc);
+        self.read(self.pc);
+        match interrupt {
+            Interrupt::Nmi => {
+                self.push_word(self.pc);
+                let mut status = self.p;
+                status.set_b(0);
+                self.push_byte(status.0);
+            }
+            // Reset goes through the same steps with its writes turned into reads.
+            Interrupt::Reset => {
+                for _ in 0..3 {
+                    self.read_stack();
+                    self.sp = self.sp.wrapping_sub(1);
+                }
+            }
+        }
+
+        let vector_address = match interrupt {
+            Interrupt::Nmi => 0xFFFA,
+            Interrupt::Reset => 0xFFFC,
+        };
+        self.pc = self.read_vector(vector_address);
+        self.p.set_i(true);
+    }
+
+    // The CPU halts on a read, waits another cycle if the copy would otherwise start on a write
+    // cycle, then takes a read and a write for each byte.
+    fn run_oam_dma(&mut self) {
+        self.read(self.pc);
+        if !self.cycles.is_multiple_of(2) {
+            self.read(self.pc);
+        }
+
+        let request = std::mem::take(&mut *self.oam_request.borrow_mut());
+        for oam_address in request.address..request.address + request.length as u16 {
+            let oam_byte = self.read(oam_address);
+            self.write(0x2004, oam_byte);
+            self.log_prg(oam_address, PRG_DATA);
+        }
+    }
+
+    // Like OAM DMA, the DMC halts the CPU on a read and waits for a read cycle to fetch on. The
+    // CPU's read is repeated on the halt, dummy and alignment cycles, then once more for real.
+    fn run_dmc_dma(&mut self, address: u16, dmc_address: u16) {
+        self.cycle();
+        self.bus.borrow_mut().read_byte(address);
+        self.cycle();
+        self.bus.borrow_mut().read_byte(address);
+        if !self.cycles.is_multiple_of(2) {
+            self.cycle();
+            self.bus.borrow_mut().read_byte(address);
+        }
 
-        Ok(cycles)
+        self.cycle();
+        let sample_byte = self.bus.borrow_mut().read_byte(dmc_address);
+        self.clock.borrow_mut().fill_dmc(sample_byte);
+        self.log_prg(dmc_address, PRG_PCM_DATA);
+    }
+
+    // Steps the rest of the console through the cycle of the access that follows.
+    fn cycle(&mut self) {
+        self.nmi_polled = self.nmi_pending;
+        if self.clock.borrow_mut().cpu_cycle() {
+            self.nmi_pending = true;
+        }
+        self.cycles += 1;
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        let dmc_address = self.clock.borrow().dmc_fetch_address();
+        if let Some(dmc_address) = dmc_address {
+            self.run_dmc_dma(address, dmc_address);
+        }
+        self.cycle();
+        self.bus.borrow_mut().read_byte(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.cycle();
+        self.bus.borrow_mut().write_byte(address, data);
+    }
+
+    fn fetch(&mut self) -> u8 {
+        let data = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        data
+    }
+
+    fn read_vector(&mut self, address: u16) -> u16 {
+        let low = self.read(address) as u16;
+        let high = self.read(address + 1) as u16;
+        self.log_prg(address, PRG_DATA);
+        self.log_prg(address + 1, PRG_DATA);
+        (high << 8) | low
     }
 
     fn set_nz_flags(&mut self, operand: u8) {
@@ -169,88 +286,133 @@ impl CPU {
         self.p.set_z(operand == 0);
     }
 
-    fn get_address(&self, address_mode: AddressMode) -> (u16, bool) {
+    /// Fetches the operand bytes and runs the rest of the address mode's cycles. An index that
+    /// crosses a page costs a dummy read from the address before its high byte is fixed; writes
+    /// always take that cycle, reads only when a page is actually crossed.
+    fn operand_address(&mut self, address_mode: AddressMode, write: bool) -> u16 {
         match address_mode {
-            AddressMode::Implied | AddressMode::Accumulator => {
+            AddressMode::Implied | AddressMode::Accumulator | AddressMode::Indirect => {
                 panic!("These should not get addresses")
             }
-            AddressMode::Immediate => (self.pc + 1, false),
-            AddressMode::ZeroPage => (self.bus.borrow_mut().read_byte(self.pc + 1) as u16, false),
-            AddressMode::ZeroPageX => (
-                (self.get_address(AddressMode::ZeroPage).0 + self.x as u16) % 256,
-                false,
-            ),
-            AddressMode::ZeroPageY => (
-                (self.get_address(AddressMode::ZeroPage).0 + self.y as u16) % 256,
-                false,
-            ),
-            AddressMode::Absolute => (self.bus.borrow_mut().read_word(self.pc + 1), false),
+            AddressMode::Immediate => {
+                let address = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                address
+            }
+            AddressMode::ZeroPage => self.fetch() as u16,
+            AddressMode::ZeroPageX | AddressMode::ZeroPageY => {
+                let address = self.fetch();
+                self.read(address as u16);
+                let index = if address_mode == AddressMode::ZeroPageX {
+                    self.x
+                } else {
+                    self.y
+                };
+                address.wrapping_add(index) as u16
+            }
+            AddressMode::Absolute => {
+                let low = self.fetch() as u16;
+                let high = self.fetch() as u16;
+                (high << 8) | low
+            }
             AddressMode::AbsoluteX => {
-                let address = self.get_address(AddressMode::Absolute).0;
-                (
-                    address + self.x as u16,
-                    address & 0xFF00 != (address + self.x as u16) & 0xFF00,
-                )
+                let address = self.operand_address(AddressMode::Absolute, write);
+                self.index_address(address, self.x, write)
             }
             AddressMode::AbsoluteY => {
-                let address = self.get_address(AddressMode::Absolute).0;
-                (
-                    address + self.y as u16,
-                    address & 0xFF00 != (address + self.y as u16) & 0xFF00,
-                )
+                let address = self.operand_address(AddressMode::Absolute, write);
+                self.index_address(address, self.y, write)
             }
             AddressMode::IndirectX => {
-                let zero_page_address = self.get_address(AddressMode::ZeroPageX).0;
-                (
-                    self.bus.borrow_mut().read_word_bug(zero_page_address),
-                    false,
-                )
+                let pointer = self.fetch();
+                self.read(pointer as u16);
+                let pointer = pointer.wrapping_add(self.x);
+                let low = self.read(pointer as u16) as u16;
+                let high = self.read(pointer.wrapping_add(1) as u16) as u16;
+                (high << 8) | low
             }
             AddressMode::IndirectY => {
-                let zero_page_address = self.get_address(AddressMode::ZeroPage).0;
-                let address = self.bus.borrow_mut().read_word_bug(zero_page_address);
-                (
-                    address + self.y as u16,
-                    address & 0xFF00 != (address + self.y as u16) & 0xFF00,
-                )
-            }
-            AddressMode::Indirect => {
-                let address = self.get_address(AddressMode::Absolute).0;
-                (self.bus.borrow_mut().read_word_bug(address), false)
+                let pointer = self.fetch();
+                let low = self.read(pointer as u16) as u16;
+                let high = self.read(pointer.wrapping_add(1) as u16) as u16;
+                self.index_address((high << 8) | low, self.y, write)
             }
         }
     }
 
-    fn read_operand(&mut self, address_mode: AddressMode) -> (u8, bool) {
+    fn index_address(&mut self, address: u16, index: u8, write: bool) -> u16 {
+        let indexed = address.wrapping_add(index as u16);
+        if write || address & 0xFF00 != indexed & 0xFF00 {
+            self.read((address & 0xFF00) | (indexed & 0xFF));
+        }
+        indexed
+    }
+
+    fn read_operand(&mut self, address_mode: AddressMode) -> u8 {
         match address_mode {
-            AddressMode::Accumulator => (self.a, false),
+            AddressMode::Accumulator => self.a,
             _ => {
-                let (address, page_cross) = self.get_address(address_mode);
-                (self.bus.borrow_mut().read_byte(address), page_cross)
+                let address = self.operand_address(address_mode, false);
+                let operand = self.read(address);
+                self.log_operand(address, address_mode);
+                operand
             }
         }
     }
 
     fn write_operand(&mut self, operand: u8, address_mode: AddressMode) {
+        let address = self.operand_address(address_mode, true);
+        self.write(address, operand);
+    }
+
+    /// Read-modify-write instructions write the old value back while they work out the new one.
+    fn modify_operand(
+        &mut self,
+        address_mode: AddressMode,
+        modify: impl FnOnce(&mut Self, u8) -> u8,
+    ) {
+        if address_mode == AddressMode::Accumulator {
+            self.a = modify(self, self.a);
+            return;
+        }
+
+        let address = self.operand_address(address_mode, true);
+        let operand = self.read(address);
+        self.log_operand(address, address_mode);
+        self.write(address, operand);
+        let result = modify(self, operand);
+        self.write(address, result);
+    }
+
+    fn log_operand(&self, address: u16, address_mode: AddressMode) {
         match address_mode {
-            AddressMode::Accumulator => self.a = operand,
-            _ => {
-                let address = self.get_address(address_mode).0;
-                self.bus.borrow_mut().write_byte(address, operand);
+            AddressMode::Immediate => {}
+            AddressMode::IndirectX | AddressMode::IndirectY => {
+                self.log_prg(address, PRG_DATA | PRG_INDIRECT_DATA)
             }
+            _ => self.log_prg(address, PRG_DATA),
+        }
+    }
+
+    fn log_prg(&self, address: u16, flags: u8) {
+        if let Some(logger) = &self.code_data_logger {
+            logger.borrow_mut().log_prg(address, flags);
         }
     }
 
     fn push_byte(&mut self, data: u8) {
-        self.bus
-            .borrow_mut()
-            .write_byte(0x100 + self.sp as u16, data);
-        self.sp -= 1;
+        self.write(0x100 + self.sp as u16, data);
+        self.sp = self.sp.wrapping_sub(1);
     }
 
     fn pop_byte(&mut self) -> u8 {
-        self.sp += 1;
-        self.bus.borrow_mut().read_byte(0x100 + self.sp as u16)
+        self.sp = self.sp.wrapping_add(1);
+        self.read(0x100 + self.sp as u16)
+    }
+
+    // Pulls waste a cycle reading the stack before the pointer is incremented.
+    fn read_stack(&mut self) {
+        self.read(0x100 + self.sp as u16);
     }
 
     fn push_word(&mut self, data: u16) {
@@ -271,3 +433,51 @@ impl CPU {
         println!("PC: ${:X}\tP: {:?}", self.pc, self.p);
     }
 }
+
+impl Savestate for CPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.a);
+        state.write_u8(self.x);
+        state.write_u8(self.y);
+        state.write_u8(self.sp);
+        state.write_u16(self.pc);
+        state.write_u8(self.p.0);
+        state.write_u8(match self.interrupt {
+            None => 0,
+            Some(Interrupt::Reset) => 1,
+            Some(Interrupt::Nmi) => 2,
+        });
+        state.write_bool(self.nmi_pending);
+        state.write_usize(self.cycles);
+
+        let oam_request = self.oam_request.borrow();
+        state.write_u16(oam_request.address);
+        state.write_usize(oam_request.length);
+
+        self.ram.borrow().save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.a = state.read_u8()?;
+        self.x = state.read_u8()?;
+        self.y = state.read_u8()?;
+        self.sp = state.read_u8()?;
+        self.pc = state.read_u16()?;
+        self.p.0 = state.read_u8()?;
+        self.interrupt = match state.read_u8()? {
+            0 => None,
+            1 => Some(Interrupt::Reset),
+            2 => Some(Interrupt::Nmi),
+            other => return Err(format!("Invalid CPU interrupt {other} in savestate")),
+        };
+        self.nmi_pending = state.read_bool()?;
+        self.nmi_polled = false;
+        self.cycles = state.read_usize()?;
+
+        let mut oam_request = self.oam_request.borrow_mut();
+        oam_request.address = state.read_u16()?;
+        oam_request.length = state.read_usize()?;
+
+        self.ram.borrow_mut().load_state(state)
+    }
+}
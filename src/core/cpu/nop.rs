@@ -1,11 +1,9 @@
 use crate::core::{cpu::AddressMode, CPU};
 
 impl CPU {
-    pub(super) fn nop(&mut self, address_mode: AddressMode) -> usize {
-        if let AddressMode::Implied = address_mode {
-            2
-        } else {
-            1 + self.read_operand(address_mode).1 as usize
+    pub(super) fn nop(&mut self, address_mode: AddressMode) {
+        if address_mode != AddressMode::Implied {
+            self.read_operand(address_mode);
         }
     }
 }
@@ -0,0 +1,162 @@
+use gilrs::ev::Button;
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+/// A button on a standard controller, or a turbo version of A or B.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ControllerButton {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+    TurboA,
+    TurboB,
+}
+
+impl ControllerButton {
+    /// The button's bit in the controller's report, which turbo buttons share with A and B.
+    pub fn bit(&self) -> u8 {
+        match self {
+            ControllerButton::A | ControllerButton::TurboA => 0x01,
+            ControllerButton::B | ControllerButton::TurboB => 0x02,
+            ControllerButton::Select => 0x04,
+            ControllerButton::Start => 0x08,
+            ControllerButton::Up => 0x10,
+            ControllerButton::Down => 0x20,
+            ControllerButton::Left => 0x40,
+            ControllerButton::Right => 0x80,
+        }
+    }
+}
+
+/// The input bound to each controller button. Buttons left out of the config are unbound.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ButtonMap<T> {
+    pub a: Option<T>,
+    pub b: Option<T>,
+    pub select: Option<T>,
+    pub start: Option<T>,
+    pub up: Option<T>,
+    pub down: Option<T>,
+    pub left: Option<T>,
+    pub right: Option<T>,
+    pub turbo_a: Option<T>,
+    pub turbo_b: Option<T>,
+}
+
+impl<T> Default for ButtonMap<T> {
+    fn default() -> Self {
+        Self {
+            a: None,
+            b: None,
+            select: None,
+            start: None,
+            up: None,
+            down: None,
+            left: None,
+            right: None,
+            turbo_a: None,
+            turbo_b: None,
+        }
+    }
+}
+
+impl<T: PartialEq> ButtonMap<T> {
+    pub fn find(&self, input: &T) -> Option<ControllerButton> {
+        [
+            (&self.a, ControllerButton::A),
+            (&self.b, ControllerButton::B),
+            (&self.select, ControllerButton::Select),
+            (&self.start, ControllerButton::Start),
+            (&self.up, ControllerButton::Up),
+            (&self.down, ControllerButton::Down),
+            (&self.left, ControllerButton::Left),
+            (&self.right, ControllerButton::Right),
+            (&self.turbo_a, ControllerButton::TurboA),
+            (&self.turbo_b, ControllerButton::TurboB),
+        ]
+        .into_iter()
+        .find(|(bound, _)| bound.as_ref() == Some(input))
+        .map(|(_, button)| button)
+    }
+}
+
+/// Everything one player can press.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerBindings {
+    pub keyboard: ButtonMap<VirtualKeyCode>,
+    pub gamepad: ButtonMap<Button>,
+    /// How far, from 0 to 1, the left stick has to be pushed to press the D-pad.
+    pub stick_threshold: f32,
+    /// Presses per second while a turbo button is held.
+    pub turbo_rate: u32,
+}
+
+impl Default for PlayerBindings {
+    fn default() -> Self {
+        Self {
+            keyboard: ButtonMap::default(),
+            gamepad: ButtonMap {
+                a: Some(Button::South),
+                b: Some(Button::East),
+                select: Some(Button::Select),
+                start: Some(Button::Start),
+                up: Some(Button::DPadUp),
+                down: Some(Button::DPadDown),
+                left: Some(Button::DPadLeft),
+                right: Some(Button::DPadRight),
+                turbo_a: Some(Button::North),
+                turbo_b: Some(Button::West),
+            },
+            stick_threshold: 0.5,
+            turbo_rate: 15,
+        }
+    }
+}
+
+impl PlayerBindings {
+    /// The defaults for each player. Only the first two get keyboard keys.
+    pub fn defaults() -> Vec<Self> {
+        let player1 = Self {
+            keyboard: ButtonMap {
+                a: Some(VirtualKeyCode::K),
+                b: Some(VirtualKeyCode::J),
+                select: Some(VirtualKeyCode::Comma),
+                start: Some(VirtualKeyCode::Period),
+                up: Some(VirtualKeyCode::W),
+                down: Some(VirtualKeyCode::S),
+                left: Some(VirtualKeyCode::A),
+                right: Some(VirtualKeyCode::D),
+                turbo_a: Some(VirtualKeyCode::I),
+                turbo_b: Some(VirtualKeyCode::U),
+            },
+            ..Self::default()
+        };
+        let player2 = Self {
+            keyboard: ButtonMap {
+                a: Some(VirtualKeyCode::Numpad2),
+                b: Some(VirtualKeyCode::Numpad1),
+                select: Some(VirtualKeyCode::Numpad4),
+                start: Some(VirtualKeyCode::Numpad5),
+                up: Some(VirtualKeyCode::Up),
+                down: Some(VirtualKeyCode::Down),
+                left: Some(VirtualKeyCode::Left),
+                right: Some(VirtualKeyCode::Right),
+                turbo_a: Some(VirtualKeyCode::Numpad3),
+                turbo_b: Some(VirtualKeyCode::Numpad0),
+            },
+            ..Self::default()
+        };
+        vec![player1, player2, Self::default(), Self::default()]
+    }
+
+    /// Frames each turbo press is held for, assuming 60 frames per second.
+    pub fn turbo_half_period(&self) -> u32 {
+        (30 / self.turbo_rate.max(1)).max(1)
+    }
+}
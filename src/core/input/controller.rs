@@ -0,0 +1,60 @@
+use super::InputDevice;
+use crate::core::{Savestate, StateReader, StateWriter};
+
+/// A standard controller: a 4021 shift register latched from the buttons while strobe is high.
+#[derive(Copy, Clone, Default)]
+pub struct Controller {
+    buttons: u8,
+    current_buttons: u8,
+    strobe: bool,
+}
+
+impl InputDevice for Controller {
+    fn strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.buttons = self.current_buttons;
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        if self.strobe {
+            self.buttons = self.current_buttons;
+        }
+        let output = self.peek();
+        // Once all eight buttons are shifted out an official controller reads back 1s.
+        self.buttons = (self.buttons >> 1) | 0x80;
+        output
+    }
+
+    fn peek(&self) -> u8 {
+        let buttons = if self.strobe {
+            self.current_buttons
+        } else {
+            self.buttons
+        };
+        buttons & 1
+    }
+
+    fn players(&self) -> usize {
+        1
+    }
+
+    fn set_buttons(&mut self, _player: usize, buttons: u8) {
+        self.current_buttons = buttons;
+    }
+}
+
+// Only the shift register is saved; the buttons currently held belong to the player.
+impl Savestate for Controller {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.buttons);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.buttons = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
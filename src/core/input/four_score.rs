@@ -0,0 +1,111 @@
+use super::{Controller, InputDevice};
+use crate::core::{Savestate, StateReader, StateWriter};
+
+const FOUR_SCORE_READS: u8 = 24;
+
+/// One side of a four player adapter, holding the controllers for two players.
+///
+/// The NES Four Score sends the first controller's eight buttons, then the second's, then a
+/// signature identifying the port, all on D0. The Famicom adapter instead reports both
+/// controllers at once, the second one on D1.
+pub struct FourScore {
+    controllers: [Controller; 2],
+    signature: Option<u8>,
+    strobe: bool,
+    reads: u8,
+}
+
+impl FourScore {
+    /// The NES Four Score half plugged into `port`, for players `port` and `port + 2`.
+    pub fn new(port: usize) -> Self {
+        Self {
+            controllers: [Controller::default(); 2],
+            signature: Some(if port == 0 { 0x10 } else { 0x20 }),
+            strobe: false,
+            reads: 0,
+        }
+    }
+
+    /// The Famicom four player adapter's lines for `port`.
+    pub fn famicom(port: usize) -> Self {
+        Self {
+            signature: None,
+            ..Self::new(port)
+        }
+    }
+
+    fn signature_bit(&self, signature: u8) -> u8 {
+        match self.reads {
+            0..=15 => 0,
+            16..=23 => (signature >> (23 - self.reads)) & 1,
+            _ => 1,
+        }
+    }
+}
+
+impl InputDevice for FourScore {
+    fn strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.reads = 0;
+        }
+        for controller in &mut self.controllers {
+            controller.strobe(strobe);
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        match self.signature {
+            None => self.controllers[0].read() | (self.controllers[1].read() << 1),
+            Some(signature) => {
+                let output = match self.reads {
+                    0..=7 => self.controllers[0].read(),
+                    8..=15 => self.controllers[1].read(),
+                    _ => self.signature_bit(signature),
+                };
+                if !self.strobe && self.reads < FOUR_SCORE_READS {
+                    self.reads += 1;
+                }
+                output
+            }
+        }
+    }
+
+    fn peek(&self) -> u8 {
+        match self.signature {
+            None => self.controllers[0].peek() | (self.controllers[1].peek() << 1),
+            Some(signature) => match self.reads {
+                0..=7 => self.controllers[0].peek(),
+                8..=15 => self.controllers[1].peek(),
+                _ => self.signature_bit(signature),
+            },
+        }
+    }
+
+    fn players(&self) -> usize {
+        self.controllers.len()
+    }
+
+    fn set_buttons(&mut self, player: usize, buttons: u8) {
+        self.controllers[player].set_buttons(0, buttons);
+    }
+}
+
+impl Savestate for FourScore {
+    fn save_state(&self, state: &mut StateWriter) {
+        for controller in &self.controllers {
+            controller.save_state(state);
+        }
+        state.write_bool(self.strobe);
+        state.write_u8(self.reads);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        for controller in &mut self.controllers {
+            controller.load_state(state)?;
+        }
+        self.strobe = state.read_bool()?;
+        self.reads = state.read_u8()?;
+        Ok(())
+    }
+}
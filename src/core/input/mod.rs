@@ -0,0 +1,418 @@
+mod bindings;
+mod controller;
+mod four_score;
+mod power_pad;
+mod provider;
+mod vaus;
+mod zapper;
+
+pub use bindings::*;
+pub use controller::*;
+pub use four_score::*;
+pub use power_pad::*;
+pub use provider::*;
+pub use vaus::*;
+pub use zapper::*;
+
+use std::str::FromStr;
+
+use gilrs::{
+    ev::{Axis, Button},
+    GamepadId,
+};
+use winit::event::{ElementState, VirtualKeyCode};
+
+use crate::core::{Addressable, Savestate, StateReader, StateWriter};
+
+const DATA_LINES: u8 = 0x1F;
+pub const MAX_PLAYERS: usize = 4;
+
+/// Something plugged into one of the $4016/$4017 ports.
+pub trait InputDevice: Savestate {
+    /// Bit 0 of a write to $4016, which every device sees.
+    fn strobe(&mut self, strobe: bool);
+    /// Data lines D0-D4 for a read of this port. The other bits are open bus.
+    fn read(&mut self) -> u8;
+    /// Same as `read` without shifting anything out.
+    fn peek(&self) -> u8;
+
+    /// Raw keys, for devices with their own layout rather than controller bindings.
+    fn input_keyboard(&mut self, _keycode: &VirtualKeyCode, _state: &ElementState) {}
+
+    /// How many players' controllers the device takes.
+    fn players(&self) -> usize {
+        0
+    }
+    /// A player's buttons for the coming frame, in `InputFrame` order.
+    fn set_buttons(&mut self, _player: usize, _buttons: u8) {}
+
+    /// Mouse position in NES pixels, or `None` when it is outside the picture.
+    fn aim(&mut self, _target: Option<(u32, u32)>) {}
+    fn mouse_button(&mut self, _pressed: bool) {}
+
+    /// Called as the PPU draws the frame, for devices that look at the picture.
+    fn sense_light(&mut self, _screen: &[u32], _scanline: u32, _cycle: u32) {}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputDeviceKind {
+    None,
+    Standard,
+    FourScore,
+    FamicomFourPlayer,
+    Zapper,
+    Vaus,
+    PowerPad,
+}
+
+impl FromStr for InputDeviceKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(InputDeviceKind::None),
+            "standard" => Ok(InputDeviceKind::Standard),
+            "four-score" => Ok(InputDeviceKind::FourScore),
+            "famicom-four-player" => Ok(InputDeviceKind::FamicomFourPlayer),
+            "zapper" => Ok(InputDeviceKind::Zapper),
+            "vaus" => Ok(InputDeviceKind::Vaus),
+            "power-pad" => Ok(InputDeviceKind::PowerPad),
+            _ => Err(format!(
+                "Unknown input device '{name}', expected none, standard, four-score, \
+                 famicom-four-player, zapper, vaus or power-pad"
+            )),
+        }
+    }
+}
+
+// Savestates store devices by their index in this list.
+const INPUT_DEVICE_KINDS: [InputDeviceKind; 7] = [
+    InputDeviceKind::None,
+    InputDeviceKind::Standard,
+    InputDeviceKind::FourScore,
+    InputDeviceKind::FamicomFourPlayer,
+    InputDeviceKind::Zapper,
+    InputDeviceKind::Vaus,
+    InputDeviceKind::PowerPad,
+];
+
+impl InputDeviceKind {
+    /// Devices for both ports from the NES 2.0 default expansion device byte.
+    pub fn from_expansion_device(id: u8) -> Option<[Self; 2]> {
+        match id {
+            0x01 => Some([InputDeviceKind::Standard, InputDeviceKind::Standard]),
+            0x02 => Some([InputDeviceKind::FourScore, InputDeviceKind::FourScore]),
+            0x03 => Some([
+                InputDeviceKind::FamicomFourPlayer,
+                InputDeviceKind::FamicomFourPlayer,
+            ]),
+            0x08 => Some([InputDeviceKind::Standard, InputDeviceKind::Zapper]),
+            0x09 => Some([InputDeviceKind::Zapper, InputDeviceKind::Zapper]),
+            0x0B | 0x0C => Some([InputDeviceKind::Standard, InputDeviceKind::PowerPad]),
+            0x0F => Some([InputDeviceKind::Standard, InputDeviceKind::Vaus]),
+            _ => None,
+        }
+    }
+
+    fn create(&self, port: usize) -> Option<Box<dyn InputDevice>> {
+        match self {
+            InputDeviceKind::None => None,
+            InputDeviceKind::Standard => Some(Box::new(Controller::default())),
+            InputDeviceKind::FourScore => Some(Box::new(FourScore::new(port))),
+            InputDeviceKind::FamicomFourPlayer => Some(Box::new(FourScore::famicom(port))),
+            InputDeviceKind::Zapper => Some(Box::new(Zapper::default())),
+            InputDeviceKind::Vaus => Some(Box::new(Vaus::default())),
+            InputDeviceKind::PowerPad => Some(Box::new(PowerPad::default())),
+        }
+    }
+}
+
+/// The two controller ports. $4016 reads port 1 and $4017 reads port 2, while a write to $4016
+/// strobes both.
+///
+/// Gamepads are given to players in the order they connect. Player `n` is routed to port
+/// `n % 2`, so with a four player adapter players 1 and 3 share port 1.
+pub struct ControllerPorts {
+    ports: [Option<Box<dyn InputDevice>>; 2],
+    devices: [InputDeviceKind; 2],
+    gamepads: [Option<GamepadId>; MAX_PLAYERS],
+    bindings: Vec<PlayerBindings>,
+    live: [LiveInput; MAX_PLAYERS],
+    frame: u32,
+}
+
+/// What a player is holding right now, before it is latched into a frame.
+#[derive(Copy, Clone, Default)]
+struct LiveInput {
+    held: u8,
+    turbo: u8,
+    stick: u8,
+}
+
+impl Default for ControllerPorts {
+    fn default() -> Self {
+        Self {
+            ports: [
+                InputDeviceKind::Standard.create(0),
+                InputDeviceKind::Standard.create(1),
+            ],
+            devices: [InputDeviceKind::Standard; 2],
+            gamepads: [None; MAX_PLAYERS],
+            bindings: PlayerBindings::defaults(),
+            live: [LiveInput::default(); MAX_PLAYERS],
+            frame: 0,
+        }
+    }
+}
+
+impl ControllerPorts {
+    /// Plugs in new devices. A four player adapter on either port takes over both.
+    pub fn set_devices(&mut self, devices: [InputDeviceKind; 2]) {
+        let devices = match devices {
+            [kind @ (InputDeviceKind::FourScore | InputDeviceKind::FamicomFourPlayer), _]
+            | [_, kind @ (InputDeviceKind::FourScore | InputDeviceKind::FamicomFourPlayer)] => {
+                [kind, kind]
+            }
+            devices => devices,
+        };
+        self.ports = [devices[0].create(0), devices[1].create(1)];
+        self.devices = devices;
+
+        let gamepads: Vec<GamepadId> = self.gamepads.iter().flatten().copied().collect();
+        self.gamepads = [None; MAX_PLAYERS];
+        for id in gamepads {
+            self.connect_gamepad(id);
+        }
+    }
+
+    pub fn devices(&self) -> [InputDeviceKind; 2] {
+        self.devices
+    }
+
+    /// Replaces the bindings for each player. Players without an entry have nothing bound.
+    pub fn set_bindings(&mut self, bindings: Vec<PlayerBindings>) {
+        self.bindings = bindings;
+        self.live = [LiveInput::default(); MAX_PLAYERS];
+    }
+
+    /// Presses the controller buttons bound to the key, then forwards it to every device.
+    pub fn input_keyboard(&mut self, keycode: &VirtualKeyCode, state: &ElementState) {
+        for player in 0..MAX_PLAYERS {
+            let button = self
+                .bindings
+                .get(player)
+                .and_then(|bindings| bindings.keyboard.find(keycode));
+            if let Some(button) = button {
+                self.set_button(player, button, *state == ElementState::Pressed);
+            }
+        }
+        for device in self.ports.iter_mut().flatten() {
+            device.input_keyboard(keycode, state);
+        }
+    }
+
+    /// Latches what every player is holding into the next frame's input, pulsing turbo buttons.
+    pub fn live_input(&mut self) -> InputFrame {
+        self.frame = self.frame.wrapping_add(1);
+        let mut input = InputFrame::default();
+        for (player, live) in self.live.iter().enumerate() {
+            let turbo_pressed = self.bindings.get(player).is_some_and(|bindings| {
+                (self.frame / bindings.turbo_half_period()).is_multiple_of(2)
+            });
+            input.buttons[player] = live.held | live.stick;
+            if turbo_pressed {
+                input.buttons[player] |= live.turbo;
+            }
+        }
+        input
+    }
+
+    /// Hands each player's buttons for the frame to the device on their port.
+    pub fn apply_input(&mut self, input: &InputFrame) {
+        for (player, &buttons) in input.buttons.iter().enumerate() {
+            if let Some((device, local_player)) = self.player_device(player) {
+                device.set_buttons(local_player, buttons);
+            }
+        }
+    }
+
+    pub fn aim(&mut self, target: Option<(u32, u32)>) {
+        for device in self.ports.iter_mut().flatten() {
+            device.aim(target);
+        }
+    }
+
+    pub fn mouse_button(&mut self, pressed: bool) {
+        for device in self.ports.iter_mut().flatten() {
+            device.mouse_button(pressed);
+        }
+    }
+
+    pub fn sense_light(&mut self, screen: &[u32], scanline: u32, cycle: u32) {
+        for device in self.ports.iter_mut().flatten() {
+            device.sense_light(screen, scanline, cycle);
+        }
+    }
+
+    /// The player's index within the device on their port, if that device takes a gamepad.
+    fn local_player(&self, player: usize) -> Option<usize> {
+        let device = self.ports[player % 2].as_ref()?;
+        let local_player = player / 2;
+        (local_player < device.players()).then_some(local_player)
+    }
+
+    fn player_device(&mut self, player: usize) -> Option<(&mut Box<dyn InputDevice>, usize)> {
+        let local_player = self.local_player(player)?;
+        Some((self.ports[player % 2].as_mut()?, local_player))
+    }
+
+    /// Gives the gamepad to the first player without one, returning that player if any.
+    pub fn connect_gamepad(&mut self, id: GamepadId) -> Option<usize> {
+        if let Some(player) = self.gamepad_player(id) {
+            return Some(player);
+        }
+        let player = (0..MAX_PLAYERS).find(|&player| {
+            self.gamepads[player].is_none() && self.local_player(player).is_some()
+        })?;
+        self.gamepads[player] = Some(id);
+        Some(player)
+    }
+
+    pub fn disconnect_gamepad(&mut self, id: GamepadId) {
+        if let Some(player) = self.gamepad_player(id) {
+            self.gamepads[player] = None;
+            self.live[player] = LiveInput::default();
+        }
+    }
+
+    pub fn gamepad_press(&mut self, id: GamepadId, button: Button) {
+        self.gamepad_button(id, button, true);
+    }
+
+    pub fn gamepad_release(&mut self, id: GamepadId, button: Button) {
+        self.gamepad_button(id, button, false);
+    }
+
+    /// Turns the left stick into D-pad presses once it passes the player's threshold.
+    pub fn gamepad_axis(&mut self, id: GamepadId, axis: Axis, value: f32) {
+        let Some(player) = self.gamepad_player(id) else {
+            return;
+        };
+        let Some(bindings) = self.bindings.get(player) else {
+            return;
+        };
+        let (negative, positive) = match axis {
+            Axis::LeftStickX => (ControllerButton::Left, ControllerButton::Right),
+            Axis::LeftStickY => (ControllerButton::Down, ControllerButton::Up),
+            _ => return,
+        };
+
+        let stick = &mut self.live[player].stick;
+        *stick &= !(negative.bit() | positive.bit());
+        if value <= -bindings.stick_threshold {
+            *stick |= negative.bit();
+        } else if value >= bindings.stick_threshold {
+            *stick |= positive.bit();
+        }
+    }
+
+    fn gamepad_button(&mut self, id: GamepadId, button: Button, pressed: bool) {
+        let Some(player) = self.gamepad_player(id) else {
+            return;
+        };
+        let button = self
+            .bindings
+            .get(player)
+            .and_then(|bindings| bindings.gamepad.find(&button));
+        if let Some(button) = button {
+            self.set_button(player, button, pressed);
+        }
+    }
+
+    fn set_button(&mut self, player: usize, button: ControllerButton, pressed: bool) {
+        let live = &mut self.live[player];
+        let buttons = match button {
+            ControllerButton::TurboA | ControllerButton::TurboB => &mut live.turbo,
+            _ => &mut live.held,
+        };
+        if pressed {
+            *buttons |= button.bit();
+        } else {
+            *buttons &= !button.bit();
+        }
+    }
+
+    fn gamepad_player(&self, id: GamepadId) -> Option<usize> {
+        self.gamepads
+            .iter()
+            .position(|&gamepad| gamepad == Some(id))
+    }
+}
+
+impl Addressable for ControllerPorts {
+    fn read_byte(&mut self, address: u16) -> Option<u8> {
+        let port = match address {
+            0x4016 => 0,
+            0x4017 => 1,
+            _ => {
+                eprintln!("Unexpected read from controller address {address}");
+                return None;
+            }
+        };
+        let data = self.ports[port].as_mut().map_or(0, |device| device.read());
+        Some(data & DATA_LINES)
+    }
+
+    fn write_byte(&mut self, address: u16, data: u8) {
+        if address == 0x4016 {
+            for device in self.ports.iter_mut().flatten() {
+                device.strobe(data & 1 > 0);
+            }
+        }
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        let port = match address {
+            0x4016 => 0,
+            0x4017 => 1,
+            _ => return None,
+        };
+        let data = self.ports[port].as_ref().map_or(0, |device| device.peek());
+        Some(data & DATA_LINES)
+    }
+
+    // Bits 5-7 are not driven by the ports, so they read back whatever was last on the data bus.
+    // That is almost always $40, the high byte of the register address.
+    fn open_bus_mask(&self, _address: u16) -> u8 {
+        !DATA_LINES
+    }
+}
+
+// The devices plugged in are saved too, so a state always loads into the devices it was made with.
+impl Savestate for ControllerPorts {
+    fn save_state(&self, state: &mut StateWriter) {
+        for kind in self.devices {
+            let index = INPUT_DEVICE_KINDS.iter().position(|&k| k == kind);
+            state.write_u8(index.unwrap_or_default() as u8);
+        }
+        for device in self.ports.iter().flatten() {
+            device.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        let mut devices = [InputDeviceKind::None; 2];
+        for kind in &mut devices {
+            *kind = *INPUT_DEVICE_KINDS
+                .get(state.read_u8()? as usize)
+                .ok_or("Invalid input device in savestate")?;
+        }
+        if devices != self.devices {
+            self.set_devices(devices);
+        }
+        for device in self.ports.iter_mut().flatten() {
+            device.load_state(state)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use winit::event::{ElementState, VirtualKeyCode};
+
+use super::InputDevice;
+use crate::core::{Savestate, StateReader, StateWriter};
+
+/// Keys for buttons 1-12, laid out like the mat: three rows of four. None of them are player 1's
+/// keys, since every device sees every key.
+const KEYMAPPING: [VirtualKeyCode; 12] = [
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::E,
+    VirtualKeyCode::R,
+    VirtualKeyCode::T,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::X,
+    VirtualKeyCode::C,
+    VirtualKeyCode::V,
+    VirtualKeyCode::B,
+];
+
+// Button numbers in the order they are shifted out on D3 and D4.
+const D3_ORDER: [usize; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const D4_ORDER: [usize; 4] = [4, 3, 12, 8];
+
+/// The Power Pad (Family Trainer) mat. Its twelve buttons are latched into two shift
+/// registers, read out on D3 and D4, and reads past the end return 1.
+#[derive(Default)]
+pub struct PowerPad {
+    buttons: u16,
+    d3: u8,
+    d4: u8,
+    strobe: bool,
+}
+
+impl PowerPad {
+    fn latch(&mut self) {
+        let pressed = |button: usize| ((self.buttons >> (button - 1)) & 1) as u8;
+        self.d3 = D3_ORDER
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &button)| bits | pressed(button) << i);
+        self.d4 = D4_ORDER
+            .iter()
+            .enumerate()
+            .fold(0xF0, |bits, (i, &button)| bits | pressed(button) << i);
+    }
+}
+
+impl InputDevice for PowerPad {
+    fn strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.latch();
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let output = self.peek();
+        self.d3 = (self.d3 >> 1) | 0x80;
+        self.d4 = (self.d4 >> 1) | 0x80;
+        output
+    }
+
+    fn peek(&self) -> u8 {
+        ((self.d4 & 1) << 4) | ((self.d3 & 1) << 3)
+    }
+
+    fn input_keyboard(&mut self, keycode: &VirtualKeyCode, state: &ElementState) {
+        if let Some(position) = KEYMAPPING.iter().position(|k| k == keycode) {
+            match state {
+                ElementState::Pressed => self.buttons |= 1 << position,
+                ElementState::Released => self.buttons &= !(1 << position),
+            }
+        }
+    }
+}
+
+// Only the shift registers are saved; the buttons currently held belong to the player.
+impl Savestate for PowerPad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.d3);
+        state.write_u8(self.d4);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.d3 = state.read_u8()?;
+        self.d4 = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
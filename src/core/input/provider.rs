@@ -0,0 +1,21 @@
+use super::MAX_PLAYERS;
+
+/// Soft reset, as if the reset button was pressed.
+pub const COMMAND_RESET: u8 = 0x01;
+/// Power cycle back to the state the console was in when the ROM was loaded.
+pub const COMMAND_POWER: u8 = 0x02;
+
+/// Everything the players did in one frame: each player's standard controller buttons, with bit
+/// 0 as A through bit 7 as Right, and any console commands.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub commands: u8,
+    pub buttons: [u8; MAX_PLAYERS],
+}
+
+/// Decides the input for each frame before it is emulated. It is given what the player is
+/// pressing right now and can pass it on, record it, or replace it, e.g. when playing back a
+/// movie.
+pub trait InputProvider {
+    fn frame_input(&mut self, live: InputFrame) -> InputFrame;
+}
@@ -0,0 +1,82 @@
+use super::InputDevice;
+use crate::{
+    core::{Savestate, StateReader, StateWriter},
+    window::NATIVE_RESOLUTION,
+};
+
+// Range of the potentiometer readings Arkanoid expects from the paddle's end stops.
+const VAUS_MIN: u32 = 0x62;
+const VAUS_MAX: u32 = 0xF2;
+
+/// The Arkanoid Vaus paddle, turned with the mouse's horizontal position.
+///
+/// A strobe latches the dial into an 8-bit shift register that is read MSB first and inverted
+/// on D4. D3 is the fire button.
+pub struct Vaus {
+    position: u8,
+    shift: u8,
+    strobe: bool,
+    button: bool,
+}
+
+impl Default for Vaus {
+    fn default() -> Self {
+        Self {
+            position: ((VAUS_MIN + VAUS_MAX) / 2) as u8,
+            shift: 0,
+            strobe: false,
+            button: false,
+        }
+    }
+}
+
+impl InputDevice for Vaus {
+    fn strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.shift = !self.position;
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        let output = self.peek();
+        if !self.strobe {
+            self.shift <<= 1;
+        }
+        output
+    }
+
+    fn peek(&self) -> u8 {
+        let shift = if self.strobe {
+            !self.position
+        } else {
+            self.shift
+        };
+        ((shift >> 7) << 4) | ((self.button as u8) << 3)
+    }
+
+    fn aim(&mut self, target: Option<(u32, u32)>) {
+        if let Some((x, _)) = target {
+            let span = VAUS_MAX - VAUS_MIN;
+            self.position = (VAUS_MIN + x * span / (NATIVE_RESOLUTION.width - 1)) as u8;
+        }
+    }
+
+    fn mouse_button(&mut self, pressed: bool) {
+        self.button = pressed;
+    }
+}
+
+// The dial position and button follow the player's mouse, so only the shift register is saved.
+impl Savestate for Vaus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.shift);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.shift = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use super::InputDevice;
+use crate::{
+    core::{Savestate, StateReader, StateWriter},
+    window::NATIVE_RESOLUTION,
+};
+
+// The photodiode keeps reporting light for a while after the beam passes, roughly this many
+// scanlines.
+const LIGHT_SCANLINES: u32 = 20;
+const LIGHT_THRESHOLD: u32 = 0xA0;
+
+/// The Zapper light gun, aimed with the mouse.
+///
+/// D3 is low while the sensor sees light and D4 is high while the trigger is held.
+#[derive(Default)]
+pub struct Zapper {
+    target: Option<(u32, u32)>,
+    trigger: bool,
+    light: bool,
+}
+
+fn brightness(pixel: u32) -> u32 {
+    let [red, green, blue, _] = pixel.to_le_bytes();
+    (red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000
+}
+
+impl InputDevice for Zapper {
+    fn strobe(&mut self, _strobe: bool) {}
+
+    fn read(&mut self) -> u8 {
+        self.peek()
+    }
+
+    fn peek(&self) -> u8 {
+        let light = if self.light { 0 } else { 0x08 };
+        let trigger = if self.trigger { 0x10 } else { 0 };
+        light | trigger
+    }
+
+    fn aim(&mut self, target: Option<(u32, u32)>) {
+        self.target = target;
+    }
+
+    fn mouse_button(&mut self, pressed: bool) {
+        self.trigger = pressed;
+    }
+
+    fn sense_light(&mut self, screen: &[u32], scanline: u32, cycle: u32) {
+        self.light = match self.target {
+            Some((x, y)) => {
+                let drawn = scanline > y || (scanline == y && cycle > x + 1);
+                drawn
+                    && scanline < y + LIGHT_SCANLINES
+                    && screen
+                        .get((y * NATIVE_RESOLUTION.width + x) as usize)
+                        .is_some_and(|&pixel| brightness(pixel) >= LIGHT_THRESHOLD)
+            }
+            None => false,
+        };
+    }
+}
+
+// The trigger and the light sensor follow the player's mouse and the picture, so neither is saved.
+impl Savestate for Zapper {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
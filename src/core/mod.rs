@@ -1,20 +1,36 @@
 mod apu;
 mod bus;
-mod controller;
+mod cdl;
+mod clock;
 mod cpu;
+mod input;
 mod ppu;
+mod region;
+mod savestate;
 
 pub use apu::*;
 pub use bus::*;
-pub use controller::*;
+pub use cdl::*;
+pub use clock::*;
 pub use cpu::*;
+pub use input::*;
 pub use ppu::*;
+pub use region::*;
+pub use savestate::*;
 
-use crate::rom::load_rom;
+use crate::audio::{AudioOutput, AudioSink};
+use crate::rom::{load_rom, Cartridge};
 use crate::window::MainWindow;
 
 use std::time::{Duration, Instant};
-use std::{cell::RefCell, fmt, fs, rc::Rc};
+use std::{
+    cell::{Ref, RefCell},
+    fmt, fs,
+    rc::Rc,
+};
+
+const SAVESTATE_MAGIC: &[u8; 4] = b"RNES";
+const SAVESTATE_VERSION: u8 = 7;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CoreError {
@@ -54,14 +70,36 @@ impl Addressable for RwAddressable {
     fn write_byte(&mut self, address: u16, data: u8) {
         self.write.borrow_mut().write_byte(address, data);
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        self.read.borrow().peek(address)
+    }
+
+    fn open_bus_mask(&self, address: u16) -> u8 {
+        self.read.borrow().open_bus_mask(address)
+    }
 }
 
 pub struct Nes {
     cpu: CPU,
+    bus: Rc<RefCell<Bus>>,
+    vram_bus: Rc<RefCell<Bus>>,
+    vram: Rc<RefCell<VRam>>,
     apu: Rc<RefCell<APU>>,
     ppu: Rc<RefCell<PPU>>,
+    cartridge: Rc<RefCell<dyn Cartridge>>,
+    clock: Rc<RefCell<SystemClock>>,
+    code_data_logger: Option<Rc<RefCell<CodeDataLogger>>>,
     frame_count_start: Instant,
-    pub controller: Rc<RefCell<Controller>>,
+    pub controllers: Rc<RefCell<ControllerPorts>>,
+    default_input_devices: [InputDeviceKind; 2],
+    input_provider: Option<Rc<RefCell<dyn InputProvider>>>,
+    pending_commands: u8,
+    power_on_state: Vec<u8>,
+    // The region `power_on_state` was taken with, the ROM header's.
+    power_on_region: Region,
+    rom_checksum: [u8; 16],
+    region: Region,
 }
 
 impl Nes {
@@ -69,16 +107,13 @@ impl Nes {
         let bus = Bus::new();
         let vram_bus = Bus::new();
 
-        let mut cpu = CPU::new(&bus);
-        cpu.set_show_ops(show_ops);
-
         let ppu = Rc::new(RefCell::new(PPU::new(vram_bus.clone())));
         bus.borrow_mut()
             .register_region(0x2000..=0x3FFF, ppu.clone());
 
-        let controller = Rc::new(RefCell::new(Controller::default()));
+        let controllers = Rc::new(RefCell::new(ControllerPorts::default()));
         bus.borrow_mut()
-            .register_region(0x4016..=0x4016, controller.clone());
+            .register_region(0x4016..=0x4016, controllers.clone());
 
         let apu = Rc::new(RefCell::new(APU::new(1.0)));
         bus.borrow_mut()
@@ -89,7 +124,7 @@ impl Nes {
         bus.borrow_mut().register_region(
             0x4017..=0x4017,
             Rc::new(RefCell::new(RwAddressable {
-                read: controller.clone(),
+                read: controllers.clone(),
                 write: apu.clone(),
             })),
         );
@@ -100,30 +135,307 @@ impl Nes {
                 return Err("Unable to read rom file.".into());
             }
         };
+        let rom_checksum = md5::compute(&rom_file[16.min(rom_file.len())..]).0;
         let vram = Rc::new(RefCell::new(VRam::default()));
         vram_bus
             .borrow_mut()
             .register_region(0x2000..=0x3FFF, vram.clone());
 
-        if let Err(e) = load_rom(&rom_file, &bus, &vram_bus, show_header, &vram) {
-            return Err(format!("Error while loading rom: {e}"));
+        let (header, cartridge) = match load_rom(&rom_file, &bus, &vram_bus, show_header, &vram) {
+            Ok(rom) => rom,
+            Err(e) => return Err(format!("Error while loading rom: {e}")),
         };
+        let default_input_devices = header
+            .default_expansion_device
+            .and_then(InputDeviceKind::from_expansion_device)
+            .unwrap_or([InputDeviceKind::Standard, InputDeviceKind::Standard]);
+        controllers.borrow_mut().set_devices(default_input_devices);
+        let region = header.region.unwrap_or_default();
+
+        let clock = SystemClock::new(
+            ppu.clone(),
+            apu.clone(),
+            controllers.clone(),
+            cartridge.clone(),
+        );
+        let mut cpu = CPU::new(&bus, &clock);
+        cpu.set_show_ops(show_ops);
 
-        Ok(Self {
+        let mut nes = Self {
             cpu,
+            bus,
+            vram_bus,
+            vram,
             apu,
             ppu,
-            controller,
+            cartridge,
+            clock,
+            code_data_logger: None,
+            controllers,
+            default_input_devices,
             frame_count_start: Instant::now(),
-        })
+            input_provider: None,
+            pending_commands: 0,
+            power_on_state: Vec::new(),
+            power_on_region: region,
+            rom_checksum,
+            region,
+        };
+        nes.set_region(region);
+        nes.power_on_state = nes.save_state();
+        Ok(nes)
+    }
+
+    pub fn cpu_bus(&self) -> &Rc<RefCell<Bus>> {
+        &self.bus
+    }
+
+    pub fn ppu_bus(&self) -> &Rc<RefCell<Bus>> {
+        &self.vram_bus
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Switches the console's timing, e.g. to override the region from the ROM header.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.borrow_mut().set_region(region);
+        self.apu.borrow_mut().set_region(region);
+        self.clock.borrow_mut().set_region(region);
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.ppu.borrow_mut().set_palette(palette);
+    }
+
+    /// The devices the ROM header asks for, or two standard controllers.
+    pub fn default_input_devices(&self) -> [InputDeviceKind; 2] {
+        self.default_input_devices
+    }
+
+    pub fn set_input_devices(&mut self, devices: [InputDeviceKind; 2]) {
+        self.controllers.borrow_mut().set_devices(devices);
+    }
+
+    /// Captures the whole machine so it can later be restored with `load_state`.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.write_bytes(SAVESTATE_MAGIC);
+        state.write_u8(SAVESTATE_VERSION);
+        state.write_u8(self.region as u8);
+        self.cpu.save_state(&mut state);
+        self.bus.borrow().save_state(&mut state);
+        self.ppu.borrow().save_state(&mut state);
+        self.vram_bus.borrow().save_state(&mut state);
+        self.vram.borrow().save_state(&mut state);
+        self.apu.borrow().save_state(&mut state);
+        self.controllers.borrow().save_state(&mut state);
+        self.cartridge.borrow().save_state(&mut state);
+        self.clock.borrow().save_state(&mut state);
+        state.into_bytes()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut state = StateReader::new(data);
+        let mut magic = [0; 4];
+        state.read_bytes(&mut magic)?;
+        if &magic != SAVESTATE_MAGIC {
+            return Err("Not an RNES savestate".into());
+        }
+        let version = state.read_u8()?;
+        if version != SAVESTATE_VERSION {
+            return Err(format!("Unsupported savestate version {version}"));
+        }
+        // Timings differ between regions, so a state only makes sense on the console it came from.
+        let region = state.read_u8()?;
+        if region != self.region as u8 {
+            let saved = [Region::Ntsc, Region::Pal, Region::Dendy]
+                .into_iter()
+                .find(|saved| *saved as u8 == region)
+                .ok_or_else(|| format!("Unknown savestate region {region}"))?;
+            return Err(format!(
+                "Savestate was made with {} timing, not {}",
+                saved.name(),
+                self.region.name()
+            ));
+        }
+
+        self.cpu.load_state(&mut state)?;
+        self.bus.borrow_mut().load_state(&mut state)?;
+        self.ppu.borrow_mut().load_state(&mut state)?;
+        self.vram_bus.borrow_mut().load_state(&mut state)?;
+        self.vram.borrow_mut().load_state(&mut state)?;
+        self.apu.borrow_mut().load_state(&mut state)?;
+        self.controllers.borrow_mut().load_state(&mut state)?;
+        self.cartridge.borrow_mut().load_state(&mut state)?;
+        self.clock.borrow_mut().load_state(&mut state)?;
+
+        if !state.is_empty() {
+            return Err("Savestate does not match this ROM".into());
+        }
+        Ok(())
     }
 
-    pub fn emulate(
-        &mut self,
-        cycles: usize,
-        screen: &mut [u32],
-        window: &MainWindow,
-    ) -> Result<(), String> {
+    /// MD5 of the ROM file after its header, as movie files use to identify the game.
+    pub fn rom_checksum(&self) -> [u8; 16] {
+        self.rom_checksum
+    }
+
+    /// Routes every frame's input through `provider`, e.g. to record or play back a movie.
+    pub fn set_input_provider(&mut self, provider: Option<Rc<RefCell<dyn InputProvider>>>) {
+        self.input_provider = provider;
+    }
+
+    /// Presses the reset button at the start of the next frame.
+    pub fn reset(&mut self) {
+        self.pending_commands |= COMMAND_RESET;
+    }
+
+    /// Turns the console off and on again at the start of the next frame.
+    pub fn power_cycle(&mut self) {
+        self.pending_commands |= COMMAND_POWER;
+    }
+
+    /// Immediately puts the console back in the state it was in right after the ROM was loaded,
+    /// keeping the input devices that are plugged in now and the current region.
+    pub fn power_on(&mut self) -> Result<(), String> {
+        let devices = self.controllers.borrow().devices();
+        let region = self.region;
+        // Nothing in the state depends on the region at power-on, so it is loaded as the region
+        // it was taken with and then switched to the current one.
+        self.region = self.power_on_region;
+        let power_on_state = std::mem::take(&mut self.power_on_state);
+        let result = self.load_state(&power_on_state);
+        self.power_on_state = power_on_state;
+        self.set_region(region);
+        self.set_input_devices(devices);
+        result
+    }
+
+    // Commands go through the input provider like the buttons do, so movies can record them.
+    fn run_commands(&mut self, commands: u8) -> Result<(), String> {
+        if commands & COMMAND_POWER > 0 {
+            self.power_on()?;
+        } else if commands & COMMAND_RESET > 0 {
+            // Like the real console, memory is left as it was and the APU channels are silenced.
+            self.cpu.reset();
+            self.apu.borrow_mut().write_byte(0x4015, 0);
+        }
+        Ok(())
+    }
+
+    fn latch_input(&mut self) -> Result<(), String> {
+        let mut input = self.controllers.borrow_mut().live_input();
+        input.commands = std::mem::take(&mut self.pending_commands);
+        if let Some(provider) = &self.input_provider {
+            input = provider.borrow_mut().frame_input(input);
+        }
+        self.run_commands(input.commands)?;
+        self.controllers.borrow_mut().apply_input(&input);
+        Ok(())
+    }
+
+    /// Keeps the audio stream running but silent, e.g. while replaying rewound frames.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.apu.borrow_mut().set_muted(muted);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.apu.borrow().volume()
+    }
+
+    /// Scales everything played, from 0 to 1. Recordings are not affected.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.apu.borrow_mut().set_volume(volume);
+    }
+
+    pub fn channel_mix(&self, channel: Channel) -> ChannelMix {
+        self.apu.borrow().channel_mix(channel)
+    }
+
+    /// Sets a channel's gain, mute and solo, which apply to recordings as well as playback.
+    pub fn set_channel_mix(&mut self, channel: Channel, mix: ChannelMix) {
+        self.apu.borrow_mut().set_channel_mix(channel, mix);
+    }
+
+    /// Plays the audio through `sink`, keeping `latency` of it queued ahead of the device.
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>, latency: Duration) {
+        self.apu
+            .borrow_mut()
+            .set_audio_output(AudioOutput::new(sink, latency, self.region));
+    }
+
+    /// Completes the audio sink's output, e.g. a WAV file's header.
+    pub fn finish_audio(&mut self) -> Result<(), String> {
+        self.apu.borrow_mut().finish_audio()
+    }
+
+    /// Matches the audio to the emulation speed, or drops it entirely with `None`.
+    pub fn set_audio_speed(&mut self, speed: Option<f32>) {
+        self.apu.borrow_mut().set_audio_speed(speed);
+    }
+
+    /// Starts or stops keeping every mixed APU sample for `take_captured_samples`, e.g. to
+    /// record the audio alongside the frames. `stems` keeps each channel's output on its own too.
+    pub fn set_sample_capture(&mut self, enabled: bool, stems: bool) {
+        self.apu.borrow_mut().set_sample_capture(enabled, stems);
+    }
+
+    /// The APU samples mixed since the last call, one per CPU cycle at `Region::cpu_clock`.
+    pub fn take_captured_samples(&mut self) -> CapturedSamples {
+        self.apu.borrow_mut().take_captured_samples()
+    }
+
+    /// Starts logging PRG and CHR ROM usage, continuing from a previous `.cdl` file if given.
+    pub fn start_code_data_log(&mut self, previous: Option<&[u8]>) -> Result<(), String> {
+        let mut logger = CodeDataLogger::new(self.cartridge.clone());
+        if let Some(previous) = previous {
+            logger.merge(previous)?;
+        }
+
+        let logger = Rc::new(RefCell::new(logger));
+        self.cpu.set_code_data_logger(Some(logger.clone()));
+        self.ppu
+            .borrow_mut()
+            .set_code_data_logger(Some(logger.clone()));
+        self.code_data_logger = Some(logger);
+
+        Ok(())
+    }
+
+    /// The current log in `.cdl` format, if logging was started.
+    pub fn code_data_log(&self) -> Option<Vec<u8>> {
+        self.code_data_logger
+            .as_ref()
+            .map(|logger| logger.borrow().to_bytes())
+    }
+
+    /// Captures the PPU viewer images the next time the PPU reaches `scanline`.
+    pub fn request_ppu_view(&mut self, scanline: u32, pattern_palette: u8) {
+        self.clock
+            .borrow_mut()
+            .request_ppu_view(scanline, pattern_palette);
+    }
+
+    /// The raw 9-bit PPU output of the last frame: palette index plus emphasis bits.
+    pub fn pixels(&self) -> Ref<'_, [u16]> {
+        Ref::map(self.clock.borrow(), |clock| clock.pixels())
+    }
+
+    /// The color subcarrier phase, from 0 to 11, at the first pixel of the last frame.
+    pub fn color_phase(&self) -> usize {
+        self.clock.borrow().color_phase()
+    }
+
+    pub fn take_ppu_view(&mut self) -> Option<PpuView> {
+        self.clock.borrow_mut().take_ppu_view()
+    }
+
+    /// Runs until the PPU finishes drawing the next frame. On NTSC that takes 29780 or 29781 CPU
+    /// cycles depending on whether the PPU skips the idle cycle of an odd frame.
+    pub fn run_frame(&mut self, screen: &mut [u32], window: &MainWindow) -> Result<(), String> {
         if self.frame_count_start.elapsed() > Duration::from_secs(1) {
             let frame_count = self.ppu.borrow().frame_count();
             let fps = frame_count as f32 / self.frame_count_start.elapsed().as_secs_f32();
@@ -132,25 +444,18 @@ impl Nes {
             self.frame_count_start = Instant::now();
         }
 
-        let mut used_cycles = 0;
-        while used_cycles < cycles {
-            match self.cpu.tick() {
-                Ok(cycle_count) => {
-                    used_cycles += cycle_count;
-                    let mut ppu = self.ppu.borrow_mut();
-                    for _ in 0..(cycle_count * 3) {
-                        if ppu.tick(screen) {
-                            self.cpu.generate_nmi();
-                        }
-                    }
-                    self.apu.borrow_mut().tick(cycle_count);
-                }
-                Err(e) => {
-                    self.cpu.dump();
-                    return Err(e.to_string());
-                }
+        self.latch_input()?;
+
+        loop {
+            if let Err(e) = self.cpu.tick() {
+                self.cpu.dump();
+                return Err(e.to_string());
+            }
+            if self.clock.borrow_mut().take_frame_complete() {
+                break;
             }
         }
+        screen.copy_from_slice(self.clock.borrow().screen());
 
         Ok(())
     }
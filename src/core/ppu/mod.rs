@@ -1,4 +1,4 @@
-use crate::core::Bus;
+use crate::core::{Bus, CodeDataLogger, Region, Savestate, StateReader, StateWriter, CHR_RENDERED};
 use crate::window::NATIVE_RESOLUTION;
 use std::{cell::RefCell, rc::Rc};
 
@@ -6,17 +6,19 @@ mod oam;
 mod palette;
 mod registers;
 mod sprite;
+mod viewer;
 mod vram;
 
-use oam::OamEntry;
+pub use oam::OamEntry;
+pub use palette::{composite_level, subcarrier_angle, yiq_to_color, Palette, PaletteAdjustments};
 use registers::{PPUAddress, PPUMask};
 use sprite::SpriteShift;
+pub use viewer::*;
 pub use vram::VRam;
 
 const NAMETABLE_BASE_ADDR: u16 = 0x2000;
 
 const MAX_CYCLE: u32 = 340;
-const MAX_SCANLINE: u32 = 261;
 
 #[derive(Default, Copy, Clone, Debug)]
 struct PPUShift {
@@ -73,6 +75,9 @@ pub struct PPU {
     vram_bus: Rc<RefCell<Bus>>,
     open_bus: u8,
     odd_frame: bool,
+    frame_complete: bool,
+    region: Region,
+    palette: Palette,
     mask: PPUMask,
     shifter: PPUShift,
     background_table: u16,
@@ -86,6 +91,7 @@ pub struct PPU {
     secondary_oam: [Option<(OamEntry, usize)>; 8],
     current_oam: [Option<(OamEntry, usize)>; 8],
     secondary_shifters: [SpriteShift; 8],
+    code_data_logger: Option<Rc<RefCell<CodeDataLogger>>>,
 }
 
 impl PPU {
@@ -108,6 +114,9 @@ impl PPU {
             vram_bus: bus,
             open_bus: 0,
             odd_frame: false,
+            frame_complete: false,
+            region: Region::default(),
+            palette: Palette::default(),
             mask: PPUMask(0),
             shifter: PPUShift::default(),
             sprite_table: 0,
@@ -122,6 +131,17 @@ impl PPU {
             current_oam: [None; 8],
             secondary_shifters: [SpriteShift::default(); 8],
             sprite_size: false,
+            code_data_logger: None,
+        }
+    }
+
+    pub fn set_code_data_logger(&mut self, logger: Option<Rc<RefCell<CodeDataLogger>>>) {
+        self.code_data_logger = logger;
+    }
+
+    fn log_chr(&self, address: u16, flags: u8) {
+        if let Some(logger) = &self.code_data_logger {
+            logger.borrow_mut().log_chr(address, flags);
         }
     }
 
@@ -133,10 +153,49 @@ impl PPU {
         self.frame_count = 0;
     }
 
-    pub fn tick(&mut self, screen: &mut [u32]) -> bool {
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// The 9-bit value the PPU outputs for a palette entry, with greyscale and emphasis from
+    /// PPUMASK applied.
+    fn pixel(&self, color: u8) -> u16 {
+        let mut pixel = color as u16 & 0x3F;
+        if self.mask.greyscale() {
+            pixel &= 0x30;
+        }
+        let (red, green) = if self.region.swaps_red_green_emphasis() {
+            (self.mask.emphasize_green(), self.mask.emphasize_red())
+        } else {
+            (self.mask.emphasize_red(), self.mask.emphasize_green())
+        };
+        pixel | (red as u16) << 6 | (green as u16) << 7 | (self.mask.emphasize_blue() as u16) << 8
+    }
+
+    /// Whether the last visible scanline has been drawn since the previous call.
+    pub fn take_frame_complete(&mut self) -> bool {
+        std::mem::take(&mut self.frame_complete)
+    }
+
+    pub fn scanline(&self) -> u32 {
+        self.scanline
+    }
+
+    pub fn cycle(&self) -> u32 {
+        self.cycle
+    }
+
+    /// Draws one dot into `screen`, and its raw 9-bit value into `pixels`. Returns true when an
+    /// NMI should be raised.
+    pub fn tick(&mut self, screen: &mut [u32], pixels: &mut [u16]) -> bool {
         let mut generate_nmi = false;
 
         if self.odd_frame
+            && self.region.skips_odd_frame_dot()
             && (self.mask.show_background() || self.mask.show_sprite())
             && self.cycle == 0
             && self.scanline == 0
@@ -144,9 +203,10 @@ impl PPU {
             self.cycle = 1;
         }
 
-        let prerender_scanline = self.scanline == MAX_SCANLINE;
-        if self.cycle == 1 && self.scanline == 241 {
+        let prerender_scanline = self.scanline == self.pre_render_scanline();
+        if self.cycle == 1 && self.scanline == self.region.vblank_scanline() {
             self.vblank = true;
+            self.frame_complete = true;
             if self.nmi_enabled {
                 generate_nmi = true;
             }
@@ -195,19 +255,19 @@ impl PPU {
                         self.attribute &= 0b11;
                     }
                     5 => {
-                        self.pattern_low = self.vram_bus.borrow_mut().read_byte(
-                            self.background_table
-                                + self.name_table_selector as u16 * 16
-                                + self.v.fine_y(),
-                        );
+                        let pattern_address = self.background_table
+                            + self.name_table_selector as u16 * 16
+                            + self.v.fine_y();
+                        self.pattern_low = self.vram_bus.borrow_mut().read_byte(pattern_address);
+                        self.log_chr(pattern_address, CHR_RENDERED);
                     }
                     7 => {
-                        self.pattern_high = self.vram_bus.borrow_mut().read_byte(
-                            self.background_table
-                                + self.name_table_selector as u16 * 16
-                                + self.v.fine_y()
-                                + 8,
-                        );
+                        let pattern_address = self.background_table
+                            + self.name_table_selector as u16 * 16
+                            + self.v.fine_y()
+                            + 8;
+                        self.pattern_high = self.vram_bus.borrow_mut().read_byte(pattern_address);
+                        self.log_chr(pattern_address, CHR_RENDERED);
                     }
                     _ => {}
                 }
@@ -252,9 +312,10 @@ impl PPU {
                 }
 
                 if (0..240).contains(&self.scanline) {
-                    screen[self.cycle as usize - 1
-                        + self.scanline as usize * NATIVE_RESOLUTION.width as usize] =
-                        palette::PALETTE[background_color as usize % 64];
+                    let index = self.cycle as usize - 1
+                        + self.scanline as usize * NATIVE_RESOLUTION.width as usize;
+                    pixels[index] = self.pixel(background_color);
+                    screen[index] = self.palette.color(pixels[index]);
                 }
             }
 
@@ -272,11 +333,15 @@ impl PPU {
         generate_nmi
     }
 
+    fn pre_render_scanline(&self) -> u32 {
+        self.region.scanlines() - 1
+    }
+
     fn increment_cycle(&mut self) {
         self.cycle += 1;
         if self.cycle > MAX_CYCLE {
             self.scanline += 1;
-            if self.scanline > MAX_SCANLINE {
+            if self.scanline > self.pre_render_scanline() {
                 self.scanline = 0;
                 self.frame_count += 1;
                 self.odd_frame = !self.odd_frame;
@@ -298,7 +363,7 @@ impl PPU {
             self.v.set_nametable_select(
                 (self.v.nametable_select() & 0b10) | (self.t.nametable_select() & 0b01),
             );
-        } else if (280..=304).contains(&self.cycle) && self.scanline == MAX_SCANLINE {
+        } else if (280..=304).contains(&self.cycle) && self.scanline == self.pre_render_scanline() {
             self.v.set_fine_y(self.t.fine_y());
             self.v.set_coarse_y(self.t.coarse_y());
             self.v.set_nametable_select(
@@ -326,3 +391,117 @@ impl PPU {
         self.v.set_fine_y(self.v.fine_y() + 1);
     }
 }
+
+impl Savestate for PPUShift {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.pattern[0]);
+        state.write_u16(self.pattern[1]);
+        state.write_u32(self.attribute);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.pattern[0] = state.read_u16()?;
+        self.pattern[1] = state.read_u16()?;
+        self.attribute = state.read_u32()?;
+        Ok(())
+    }
+}
+
+fn save_sprite_slots(slots: &[Option<(OamEntry, usize)>], state: &mut StateWriter) {
+    for slot in slots {
+        state.write_bool(slot.is_some());
+        let (entry, index) = slot.unwrap_or_default();
+        entry.save_state(state);
+        state.write_usize(index);
+    }
+}
+
+fn load_sprite_slots(
+    slots: &mut [Option<(OamEntry, usize)>],
+    state: &mut StateReader,
+) -> Result<(), String> {
+    for slot in slots {
+        let present = state.read_bool()?;
+        let mut entry = OamEntry::default();
+        entry.load_state(state)?;
+        let index = state.read_usize()?;
+        *slot = present.then_some((entry, index));
+    }
+    Ok(())
+}
+
+impl Savestate for PPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.t.0);
+        state.write_u16(self.v.0);
+        state.write_bool(self.w);
+        state.write_u32(self.cycle);
+        state.write_u32(self.scanline);
+        state.write_u16(self.increment_size);
+        state.write_bool(self.nmi_enabled);
+        state.write_bool(self.vblank);
+        state.write_bool(self.sprite0_hit);
+        state.write_bool(self.sprite_overflow);
+        state.write_bool(self.sprite_size);
+        state.write_bool(self.reset);
+        state.write_u8(self.fine_x);
+        state.write_u8(self.internal_data_buffer);
+        state.write_u8(self.open_bus);
+        state.write_bool(self.odd_frame);
+        state.write_u8(self.mask.0);
+        self.shifter.save_state(state);
+        state.write_u16(self.background_table);
+        state.write_u16(self.sprite_table);
+        state.write_u8(self.name_table_selector);
+        state.write_u8(self.pattern_low);
+        state.write_u8(self.pattern_high);
+        state.write_u8(self.attribute);
+        state.write_u8(self.oam_address);
+        for entry in &self.primary_oam {
+            entry.save_state(state);
+        }
+        save_sprite_slots(&self.secondary_oam, state);
+        save_sprite_slots(&self.current_oam, state);
+        for shifter in &self.secondary_shifters {
+            shifter.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.t.0 = state.read_u16()?;
+        self.v.0 = state.read_u16()?;
+        self.w = state.read_bool()?;
+        self.cycle = state.read_u32()?;
+        self.scanline = state.read_u32()?;
+        self.increment_size = state.read_u16()?;
+        self.nmi_enabled = state.read_bool()?;
+        self.vblank = state.read_bool()?;
+        self.sprite0_hit = state.read_bool()?;
+        self.sprite_overflow = state.read_bool()?;
+        self.sprite_size = state.read_bool()?;
+        self.reset = state.read_bool()?;
+        self.fine_x = state.read_u8()?;
+        self.internal_data_buffer = state.read_u8()?;
+        self.open_bus = state.read_u8()?;
+        self.odd_frame = state.read_bool()?;
+        self.mask.0 = state.read_u8()?;
+        self.shifter.load_state(state)?;
+        self.background_table = state.read_u16()?;
+        self.sprite_table = state.read_u16()?;
+        self.name_table_selector = state.read_u8()?;
+        self.pattern_low = state.read_u8()?;
+        self.pattern_high = state.read_u8()?;
+        self.attribute = state.read_u8()?;
+        self.oam_address = state.read_u8()?;
+        for entry in &mut self.primary_oam {
+            entry.load_state(state)?;
+        }
+        load_sprite_slots(&mut self.secondary_oam, state)?;
+        load_sprite_slots(&mut self.current_oam, state)?;
+        for shifter in &mut self.secondary_shifters {
+            shifter.load_state(state)?;
+        }
+        self.frame_complete = false;
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::core::{Savestate, StateReader, StateWriter};
+
 #[derive(Copy, Clone, Debug)]
 pub struct OamEntry {
     pub y: u8,
@@ -16,3 +18,16 @@ impl Default for OamEntry {
         }
     }
 }
+
+impl Savestate for OamEntry {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&[self.y, self.tile_index, self.attributes, self.x]);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        let mut bytes = [0; 4];
+        state.read_bytes(&mut bytes)?;
+        [self.y, self.tile_index, self.attributes, self.x] = bytes;
+        Ok(())
+    }
+}
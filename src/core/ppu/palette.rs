@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+
 pub const PALETTE: [u32; 64] = [
     0xff666666, 0xff882a00, 0xffa71214, 0xffa4003b, 0xff7e005c, 0xff40006e, 0xff00066c, 0xff001d56,
     0xff003533, 0xff00480b, 0xff005200, 0xff084f00, 0xff4d4000, 0xff000000, 0xff000000, 0xff000000,
@@ -8,3 +10,186 @@ pub const PALETTE: [u32; 64] = [
     0xfffffeff, 0xffffdfc0, 0xffffd2d3, 0xffffc8e8, 0xffffc2fb, 0xffeac4fe, 0xffc5ccfe, 0xffa5d8f7,
     0xff94e5e4, 0xff96efcf, 0xffabf4bd, 0xffccf3b3, 0xfff2ebb5, 0xffb8b8b8, 0xff000000, 0xff000000,
 ];
+
+/// Colors for every 9-bit PPU pixel: the 6-bit palette index plus the three emphasis bits.
+pub const PALETTE_SIZE: usize = 512;
+
+// Composite signal levels of the PPU's output, relative to sync, from Bisqwit's measurements.
+const SIGNAL_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const SIGNAL_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const SIGNAL_BLACK: f32 = 0.518;
+const SIGNAL_WHITE: f32 = 1.962;
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+
+/// Picture controls for `Palette::ntsc`, at their neutral values by default.
+#[derive(Copy, Clone, Debug)]
+pub struct PaletteAdjustments {
+    /// Rotation of every hue, in degrees.
+    pub hue: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    /// Added to every level, where 1.0 is the difference between black and white.
+    pub brightness: f32,
+}
+
+impl Default for PaletteAdjustments {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+        }
+    }
+}
+
+/// Maps 9-bit PPU pixels to the colors drawn on screen.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    colors: Vec<u32>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::from_base_colors(&PALETTE)
+    }
+}
+
+impl Palette {
+    /// Reads a `.pal` file of RGB triplets, either the 64 base colors or all 512 emphasis
+    /// combinations.
+    pub fn from_pal(data: &[u8]) -> Result<Self, String> {
+        let colors: Vec<u32> = data
+            .chunks_exact(3)
+            .map(|rgb| pack_rgb(rgb[0] as f32, rgb[1] as f32, rgb[2] as f32))
+            .collect();
+        match (data.len() % 3, colors.len()) {
+            (0, 64) => Ok(Self::from_base_colors(&colors)),
+            (0, PALETTE_SIZE) => Ok(Self { colors }),
+            _ => Err(format!(
+                "Palette files must hold 64 or 512 RGB colors, not {} bytes",
+                data.len()
+            )),
+        }
+    }
+
+    /// Decodes the PPU's composite signal the way an NTSC television would. Unlike a `.pal` file
+    /// with only 64 colors, this also gets emphasis right since it happens in the signal.
+    pub fn ntsc(adjustments: &PaletteAdjustments) -> Self {
+        let hue = adjustments.hue.to_radians();
+        let colors = (0..PALETTE_SIZE as u16)
+            .map(|pixel| {
+                let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+                for phase in 0..12 {
+                    let level = composite_level(pixel, phase) / 12.0;
+                    let angle = subcarrier_angle(phase) + hue;
+                    y += level;
+                    i += level * angle.cos();
+                    q += level * angle.sin();
+                }
+                yiq_to_color(y, i, q, adjustments)
+            })
+            .collect();
+        Self { colors }
+    }
+
+    /// The color of a 9-bit pixel.
+    pub fn color(&self, pixel: u16) -> u32 {
+        self.colors[pixel as usize % PALETTE_SIZE]
+    }
+
+    // Emphasis dims the channels that are not emphasized, approximated here on RGB.
+    fn from_base_colors(base: &[u32]) -> Self {
+        let colors = (0..PALETTE_SIZE)
+            .map(|pixel| {
+                let color = base[pixel % 64];
+                let emphasis = pixel >> 6;
+                if emphasis == 0 {
+                    return color;
+                }
+                let channel = |shift: u32, emphasized: bool| {
+                    let value = ((color >> shift) & 0xFF) as f32;
+                    if emphasized {
+                        value
+                    } else {
+                        value * EMPHASIS_ATTENUATION
+                    }
+                };
+                let only = |bit: usize| emphasis & !bit == 0;
+                pack_rgb(
+                    channel(0, only(1)),
+                    channel(8, only(2)),
+                    channel(16, only(4)),
+                )
+            })
+            .collect();
+        Self { colors }
+    }
+}
+
+/// The PPU's composite output for a 9-bit pixel at one of the twelve phases of the color
+/// subcarrier, scaled so black is 0 and white is 1. Each color is a square wave that is high for
+/// half of the phases, and each emphasis bit attenuates a different third of them.
+pub fn composite_level(pixel: u16, phase: usize) -> f32 {
+    let color = pixel as usize & 0x0F;
+    let level = if color > 0x0D {
+        1
+    } else {
+        (pixel as usize >> 4) & 3
+    };
+    let emphasis = pixel >> 6;
+
+    let high = if in_color_phase(color, phase) {
+        color < 0x0D
+    } else {
+        color == 0x00
+    };
+    let mut signal = if high {
+        SIGNAL_HIGH[level]
+    } else {
+        SIGNAL_LOW[level]
+    };
+    if (emphasis & 1 > 0 && in_color_phase(0x0C, phase))
+        || (emphasis & 2 > 0 && in_color_phase(0x04, phase))
+        || (emphasis & 4 > 0 && in_color_phase(0x08, phase))
+    {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+    (signal - SIGNAL_BLACK) / (SIGNAL_WHITE - SIGNAL_BLACK)
+}
+
+/// Where the demodulator's reference wave is at a subcarrier phase, in radians.
+pub fn subcarrier_angle(phase: usize) -> f32 {
+    PI * (phase % 12) as f32 / 6.0
+}
+
+/// Turns a demodulated signal into a color, with the picture controls other than hue applied.
+pub fn yiq_to_color(y: f32, i: f32, q: f32, adjustments: &PaletteAdjustments) -> u32 {
+    let y = (y - 0.5) * adjustments.contrast + 0.5 + adjustments.brightness;
+    let i = i * adjustments.saturation * adjustments.contrast;
+    let q = q * adjustments.saturation * adjustments.contrast;
+    pack_rgb(
+        gamma(y + 0.946882 * i + 0.623557 * q),
+        gamma(y - 0.274788 * i - 0.635691 * q),
+        gamma(y - 1.108545 * i + 1.709007 * q),
+    )
+}
+
+fn in_color_phase(color: usize, phase: usize) -> bool {
+    (color + phase + 8) % 12 < 6
+}
+
+// Linear light to 0-255, for a television gamma of 1.8 seen on a 2.2 display.
+fn gamma(value: f32) -> f32 {
+    if value <= 0.0 {
+        0.0
+    } else {
+        value.powf(2.2 / 1.8) * 255.0
+    }
+}
+
+// Colors are RGBA bytes, so red is the low byte.
+fn pack_rgb(red: f32, green: f32, blue: f32) -> u32 {
+    let byte = |value: f32| value.clamp(0.0, 255.0) as u32;
+    0xff000000 | (byte(blue) << 16) | (byte(green) << 8) | byte(red)
+}
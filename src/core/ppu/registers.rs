@@ -1,4 +1,4 @@
-use crate::core::{Addressable, PPU};
+use crate::core::{Addressable, CHR_READ, PPU};
 
 use bitfield::bitfield;
 
@@ -75,15 +75,13 @@ impl Addressable for PPU {
             PPUCTRL | PPUMASK | OAMADDR | PPUSCROLL | PPUADDR => self.open_bus,
             PPUSTATUS => {
                 self.w = false;
-                let result = ((self.vblank as u8) << 7)
-                    | ((self.sprite0_hit as u8) << 6)
-                    | ((self.sprite_overflow as u8) << 5)
-                    | (self.open_bus & 0x1F);
+                let result = self.status();
                 self.vblank = false;
                 result
             }
             PPUDATA => {
                 let mut read_byte = self.vram_bus.borrow_mut().read_byte(self.v.0);
+                self.log_chr(self.v.0, CHR_READ);
                 if self.v.0 < 0x3F00 {
                     std::mem::swap(&mut self.internal_data_buffer, &mut read_byte);
                 } else {
@@ -93,13 +91,7 @@ impl Addressable for PPU {
                 self.v.0 = (self.v.0 + self.increment_size) & 0x3FFF;
                 read_byte
             }
-            OAMDATA => match self.oam_address % 4 {
-                0 => self.primary_oam[self.oam_address as usize / 4].y,
-                1 => self.primary_oam[self.oam_address as usize / 4].tile_index,
-                2 => self.primary_oam[self.oam_address as usize / 4].attributes,
-                3 => self.primary_oam[self.oam_address as usize / 4].x,
-                _ => unreachable!(),
-            },
+            OAMDATA => self.oam_data(),
             _ => {
                 unimplemented!("Reading from VRAM at {address:X}");
             }
@@ -186,4 +178,41 @@ impl Addressable for PPU {
             }
         }
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        let address = address % 8 + 0x2000;
+
+        match address {
+            PPUSTATUS => Some(self.status()),
+            PPUDATA => {
+                if self.v.0 < 0x3F00 {
+                    Some(self.internal_data_buffer)
+                } else {
+                    Some(self.vram_bus.borrow().peek_byte(self.v.0))
+                }
+            }
+            OAMDATA => Some(self.oam_data()),
+            _ => Some(self.open_bus),
+        }
+    }
+}
+
+impl PPU {
+    fn status(&self) -> u8 {
+        ((self.vblank as u8) << 7)
+            | ((self.sprite0_hit as u8) << 6)
+            | ((self.sprite_overflow as u8) << 5)
+            | (self.open_bus & 0x1F)
+    }
+
+    fn oam_data(&self) -> u8 {
+        let entry = &self.primary_oam[self.oam_address as usize / 4];
+        match self.oam_address % 4 {
+            0 => entry.y,
+            1 => entry.tile_index,
+            2 => entry.attributes,
+            3 => entry.x,
+            _ => unreachable!(),
+        }
+    }
 }
@@ -1,4 +1,5 @@
-use super::PPU;
+use super::{CHR_RENDERED, PPU};
+use crate::core::{Savestate, StateReader, StateWriter};
 
 #[derive(Default, Copy, Clone, Debug)]
 pub struct SpriteShift {
@@ -68,11 +69,15 @@ impl PPU {
                 } else {
                     self.sprite_table
                 };
+                let pattern_address = sprite_table + tile_index * 16 + (y % 8);
                 self.secondary_shifters[i] = SpriteShift {
-                    pattern_low: vram_bus.read_byte(sprite_table + tile_index * 16 + (y % 8)),
-                    pattern_high: vram_bus.read_byte(sprite_table + tile_index * 16 + (y % 8) + 8),
+                    pattern_low: vram_bus.read_byte(pattern_address),
+                    pattern_high: vram_bus.read_byte(pattern_address + 8),
                     attribute: entry.attributes,
-                }
+                };
+                drop(vram_bus);
+                self.log_chr(pattern_address, CHR_RENDERED);
+                self.log_chr(pattern_address + 8, CHR_RENDERED);
             }
         }
     }
@@ -97,3 +102,18 @@ impl PPU {
         pattern.unwrap_or((0, 0, false))
     }
 }
+
+impl Savestate for SpriteShift {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.pattern_low);
+        state.write_u8(self.pattern_high);
+        state.write_u8(self.attribute);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.pattern_low = state.read_u8()?;
+        self.pattern_high = state.read_u8()?;
+        self.attribute = state.read_u8()?;
+        Ok(())
+    }
+}
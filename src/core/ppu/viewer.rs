@@ -0,0 +1,239 @@
+use super::{OamEntry, NAMETABLE_BASE_ADDR, PPU};
+
+const TILE_SIZE: usize = 8;
+const NAMETABLE_WIDTH: usize = 256;
+const NAMETABLE_HEIGHT: usize = 240;
+const PATTERN_TABLE_SIZE: usize = 128;
+const PALETTE_SWATCH_SIZE: usize = 16;
+const SPRITE_CELL_WIDTH: usize = 8;
+const SPRITE_CELL_HEIGHT: usize = 16;
+const SPRITES_PER_ROW: usize = 8;
+
+const VIEWPORT_COLOR: u32 = 0xff00ffff;
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0xff000000; width * height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: u32) {
+        self.pixels[x + y * self.width] = color;
+    }
+}
+
+/// Snapshot of PPU memory rendered into images for debugging.
+pub struct PpuView {
+    pub scanline: u32,
+    /// All four nametables in a 2x2 grid with the scroll viewport outlined.
+    pub nametables: Image,
+    /// Both pattern tables side by side using `pattern_palette`.
+    pub pattern_tables: Image,
+    pub pattern_palette: u8,
+    /// The 32 palette RAM entries, background palettes on the first row.
+    pub palette: Image,
+    /// Each OAM entry drawn into its own cell, eight sprites per row.
+    pub sprites: Image,
+    pub oam: [OamEntry; 64],
+}
+
+impl PPU {
+    pub fn capture_view(&self, pattern_palette: u8) -> PpuView {
+        PpuView {
+            scanline: self.scanline,
+            nametables: self.render_nametables(),
+            pattern_tables: self.render_pattern_tables(pattern_palette),
+            pattern_palette,
+            palette: self.render_palette(),
+            sprites: self.render_sprites(),
+            oam: self.primary_oam,
+        }
+    }
+
+    fn palette_color(&self, palette_address: u16) -> u32 {
+        let color = self.vram_bus.borrow().peek_byte(0x3F00 + palette_address);
+        self.palette.color(color as u16)
+    }
+
+    fn tile_row(&self, pattern_address: u16) -> [u8; TILE_SIZE] {
+        let vram_bus = self.vram_bus.borrow();
+        let low = vram_bus.peek_byte(pattern_address);
+        let high = vram_bus.peek_byte(pattern_address + 8);
+
+        let mut row = [0; TILE_SIZE];
+        for (i, pixel) in row.iter_mut().enumerate() {
+            let bit = 7 - i;
+            *pixel = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+        }
+        row
+    }
+
+    fn render_nametables(&self) -> Image {
+        let mut image = Image::new(NAMETABLE_WIDTH * 2, NAMETABLE_HEIGHT * 2);
+
+        for nametable in 0..4u16 {
+            let base = NAMETABLE_BASE_ADDR + nametable * 0x400;
+            let origin_x = (nametable as usize % 2) * NAMETABLE_WIDTH;
+            let origin_y = (nametable as usize / 2) * NAMETABLE_HEIGHT;
+
+            for tile_y in 0..30u16 {
+                for tile_x in 0..32u16 {
+                    let vram_bus = self.vram_bus.borrow();
+                    let tile = vram_bus.peek_byte(base + tile_y * 32 + tile_x);
+                    let attribute =
+                        vram_bus.peek_byte(base + 0x3C0 + (tile_y / 4) * 8 + tile_x / 4);
+                    let palette_index =
+                        (attribute >> ((tile_x & 0b10) | ((tile_y & 0b10) << 1))) & 0b11;
+
+                    for fine_y in 0..TILE_SIZE {
+                        let row =
+                            self.tile_row(self.background_table + tile as u16 * 16 + fine_y as u16);
+                        for (fine_x, &pixel) in row.iter().enumerate() {
+                            let color = match pixel {
+                                0 => self.palette_color(0),
+                                _ => self.palette_color((palette_index * 4 + pixel) as u16),
+                            };
+                            image.set(
+                                origin_x + tile_x as usize * TILE_SIZE + fine_x,
+                                origin_y + tile_y as usize * TILE_SIZE + fine_y,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        self.draw_viewport(&mut image);
+
+        image
+    }
+
+    fn draw_viewport(&self, image: &mut Image) {
+        let scroll_x = (self.t.nametable_select() as usize & 1) * NAMETABLE_WIDTH
+            + self.t.coarse_x() as usize * TILE_SIZE
+            + self.fine_x as usize;
+        let scroll_y = (self.t.nametable_select() as usize >> 1) * NAMETABLE_HEIGHT
+            + self.t.coarse_y() as usize * TILE_SIZE
+            + self.t.fine_y() as usize;
+
+        for offset in 0..NAMETABLE_WIDTH {
+            let x = (scroll_x + offset) % image.width;
+            image.set(x, scroll_y % image.height, VIEWPORT_COLOR);
+            image.set(
+                x,
+                (scroll_y + NAMETABLE_HEIGHT - 1) % image.height,
+                VIEWPORT_COLOR,
+            );
+        }
+        for offset in 0..NAMETABLE_HEIGHT {
+            let y = (scroll_y + offset) % image.height;
+            image.set(scroll_x % image.width, y, VIEWPORT_COLOR);
+            image.set(
+                (scroll_x + NAMETABLE_WIDTH - 1) % image.width,
+                y,
+                VIEWPORT_COLOR,
+            );
+        }
+    }
+
+    fn render_pattern_tables(&self, pattern_palette: u8) -> Image {
+        let mut image = Image::new(PATTERN_TABLE_SIZE * 2, PATTERN_TABLE_SIZE);
+        let palette_base = (pattern_palette as u16 % 8) * 4;
+
+        for table in 0..2usize {
+            for tile in 0..256usize {
+                let origin_x = table * PATTERN_TABLE_SIZE + (tile % 16) * TILE_SIZE;
+                let origin_y = (tile / 16) * TILE_SIZE;
+                for fine_y in 0..TILE_SIZE {
+                    let row = self.tile_row((table * 0x1000 + tile * 16 + fine_y) as u16);
+                    for (fine_x, &pixel) in row.iter().enumerate() {
+                        image.set(
+                            origin_x + fine_x,
+                            origin_y + fine_y,
+                            self.palette_color(palette_base + pixel as u16),
+                        );
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
+    fn render_palette(&self) -> Image {
+        let mut image = Image::new(PALETTE_SWATCH_SIZE * 16, PALETTE_SWATCH_SIZE * 2);
+
+        for entry in 0..32usize {
+            let color = self.palette_color(entry as u16);
+            let origin_x = (entry % 16) * PALETTE_SWATCH_SIZE;
+            let origin_y = (entry / 16) * PALETTE_SWATCH_SIZE;
+            for y in 0..PALETTE_SWATCH_SIZE {
+                for x in 0..PALETTE_SWATCH_SIZE {
+                    image.set(origin_x + x, origin_y + y, color);
+                }
+            }
+        }
+
+        image
+    }
+
+    fn render_sprites(&self) -> Image {
+        let rows = self.primary_oam.len() / SPRITES_PER_ROW;
+        let mut image = Image::new(
+            SPRITES_PER_ROW * SPRITE_CELL_WIDTH,
+            rows * SPRITE_CELL_HEIGHT,
+        );
+        let sprite_height = if self.sprite_size { 16 } else { 8 };
+        let background = self.palette_color(0);
+
+        for (i, entry) in self.primary_oam.iter().enumerate() {
+            let origin_x = (i % SPRITES_PER_ROW) * SPRITE_CELL_WIDTH;
+            let origin_y = (i / SPRITES_PER_ROW) * SPRITE_CELL_HEIGHT;
+            let palette_base = 0x10 + (entry.attributes as u16 & 3) * 4;
+            let flip_horizontal = entry.attributes & 0x40 > 0;
+            let flip_vertical = entry.attributes & 0x80 > 0;
+
+            for y in 0..sprite_height {
+                let sprite_y = if flip_vertical {
+                    sprite_height - 1 - y
+                } else {
+                    y
+                };
+                let (table, tile) = if self.sprite_size {
+                    (
+                        (entry.tile_index & 1) as u16 * 0x1000,
+                        (entry.tile_index & 0xFE) as u16 + (sprite_y / 8) as u16,
+                    )
+                } else {
+                    (self.sprite_table, entry.tile_index as u16)
+                };
+                let row = self.tile_row(table + tile * 16 + (sprite_y % 8) as u16);
+
+                for x in 0..TILE_SIZE {
+                    let pixel = if flip_horizontal {
+                        row[TILE_SIZE - 1 - x]
+                    } else {
+                        row[x]
+                    };
+                    let color = match pixel {
+                        0 => background,
+                        _ => self.palette_color(palette_base + pixel as u16),
+                    };
+                    image.set(origin_x + x, origin_y + y, color);
+                }
+            }
+        }
+
+        image
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{core::Addressable, rom::MirrorArrangement};
+use crate::{
+    core::{Addressable, Savestate, StateReader, StateWriter},
+    rom::MirrorArrangement,
+};
 
 pub struct VRam {
     nametable0: [u8; 0x400],
@@ -26,68 +29,101 @@ impl VRam {
 
 impl Addressable for VRam {
     fn read_byte(&mut self, address: u16) -> Option<u8> {
+        let data = self.peek(address);
+        if data.is_none() {
+            eprintln!("Unexpected VRAM read at {address:X}");
+        }
+        data
+    }
+
+    fn write_byte(&mut self, address: u16, data: u8) {
         match address {
             0x2000..=0x2FFF => {
                 let address = address as usize;
                 match self.mirroring {
                     MirrorArrangement::Vertical => match address {
-                        0x2000..=0x23FF => Some(self.nametable0[address - 0x2000]),
-                        0x2400..=0x27FF => Some(self.nametable1[address - 0x2400]),
-                        0x2800..=0x2BFF => Some(self.nametable0[address - 0x2800]),
-                        0x2C00..=0x2FFF => Some(self.nametable1[address - 0x2C00]),
+                        0x2000..=0x23FF => self.nametable0[address - 0x2000] = data,
+                        0x2400..=0x27FF => self.nametable1[address - 0x2400] = data,
+                        0x2800..=0x2BFF => self.nametable0[address - 0x2800] = data,
+                        0x2C00..=0x2FFF => self.nametable1[address - 0x2C00] = data,
                         _ => unreachable!(),
                     },
                     MirrorArrangement::Horizontal => match address {
-                        0x2000..=0x27FF => Some(self.nametable0[address % 0x400]),
-                        0x2800..=0x2FFF => Some(self.nametable1[address % 0x400]),
+                        0x2000..=0x27FF => self.nametable0[address % 0x400] = data,
+                        0x2800..=0x2FFF => self.nametable1[address % 0x400] = data,
                         _ => unreachable!(),
                     },
-                    MirrorArrangement::OneScreenLower => Some(self.nametable0[address % 0x400]),
-                    MirrorArrangement::OneScreenUpper => Some(self.nametable1[address % 0x400]),
+                    MirrorArrangement::OneScreenLower => self.nametable0[address % 0x400] = data,
+                    MirrorArrangement::OneScreenUpper => self.nametable1[address % 0x400] = data,
                 }
             }
-            0x3000..=0x3EFF => self.read_byte(address - 0x1000),
+            0x3000..=0x3EFF => self.write_byte(address - 0x1000, data),
             0x3F00..=0x3FFF => match address {
-                0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => self.read_byte(address - 0x10),
-                0x3F04 | 0x3F08 | 0x3F0C => Some(self.palette[address as usize % 4]),
-                _ => Some(self.palette[(address as usize - 0x3F00) % 0x20]),
+                0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => self.write_byte(address - 0x10, data),
+                _ => self.palette[(address as usize - 0x3F00) % 0x20] = data,
             },
             _ => {
-                eprintln!("Unexpected VRAM read at {address:X}");
-                None
+                eprintln!("Unexpected VRAM write at {address:X}");
             }
         }
     }
 
-    fn write_byte(&mut self, address: u16, data: u8) {
+    fn peek(&self, address: u16) -> Option<u8> {
         match address {
             0x2000..=0x2FFF => {
                 let address = address as usize;
                 match self.mirroring {
                     MirrorArrangement::Vertical => match address {
-                        0x2000..=0x23FF => self.nametable0[address - 0x2000] = data,
-                        0x2400..=0x27FF => self.nametable1[address - 0x2400] = data,
-                        0x2800..=0x2BFF => self.nametable0[address - 0x2800] = data,
-                        0x2C00..=0x2FFF => self.nametable1[address - 0x2C00] = data,
+                        0x2000..=0x23FF => Some(self.nametable0[address - 0x2000]),
+                        0x2400..=0x27FF => Some(self.nametable1[address - 0x2400]),
+                        0x2800..=0x2BFF => Some(self.nametable0[address - 0x2800]),
+                        0x2C00..=0x2FFF => Some(self.nametable1[address - 0x2C00]),
                         _ => unreachable!(),
                     },
                     MirrorArrangement::Horizontal => match address {
-                        0x2000..=0x27FF => self.nametable0[address % 0x400] = data,
-                        0x2800..=0x2FFF => self.nametable1[address % 0x400] = data,
+                        0x2000..=0x27FF => Some(self.nametable0[address % 0x400]),
+                        0x2800..=0x2FFF => Some(self.nametable1[address % 0x400]),
                         _ => unreachable!(),
                     },
-                    MirrorArrangement::OneScreenLower => self.nametable0[address % 0x400] = data,
-                    MirrorArrangement::OneScreenUpper => self.nametable1[address % 0x400] = data,
+                    MirrorArrangement::OneScreenLower => Some(self.nametable0[address % 0x400]),
+                    MirrorArrangement::OneScreenUpper => Some(self.nametable1[address % 0x400]),
                 }
             }
-            0x3000..=0x3EFF => self.write_byte(address - 0x1000, data),
+            0x3000..=0x3EFF => self.peek(address - 0x1000),
             0x3F00..=0x3FFF => match address {
-                0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => self.write_byte(address - 0x10, data),
-                _ => self.palette[(address as usize - 0x3F00) % 0x20] = data,
+                0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => self.peek(address - 0x10),
+                0x3F04 | 0x3F08 | 0x3F0C => Some(self.palette[address as usize % 4]),
+                _ => Some(self.palette[(address as usize - 0x3F00) % 0x20]),
             },
-            _ => {
-                eprintln!("Unexpected VRAM write at {address:X}");
-            }
+            _ => None,
         }
     }
 }
+
+impl Savestate for VRam {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.nametable0);
+        state.write_bytes(&self.nametable1);
+        state.write_bytes(&self.palette);
+        state.write_u8(match self.mirroring {
+            MirrorArrangement::OneScreenLower => 0,
+            MirrorArrangement::OneScreenUpper => 1,
+            MirrorArrangement::Horizontal => 2,
+            MirrorArrangement::Vertical => 3,
+        });
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.nametable0)?;
+        state.read_bytes(&mut self.nametable1)?;
+        state.read_bytes(&mut self.palette)?;
+        self.mirroring = match state.read_u8()? {
+            0 => MirrorArrangement::OneScreenLower,
+            1 => MirrorArrangement::OneScreenUpper,
+            2 => MirrorArrangement::Horizontal,
+            3 => MirrorArrangement::Vertical,
+            other => return Err(format!("Invalid mirroring {other} in savestate")),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::str::FromStr;
+
+const NTSC_NOISE_PERIOD: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_NOISE_PERIOD: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+const NTSC_DMC_RATE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_DMC_RATE: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// The console's TV system, which sets the clock rates and the shape of a frame.
+///
+/// The Dendy is a Famiclone with PAL's 312 scanlines but NTSC's 1:3 CPU to PPU ratio and APU.
+/// It keeps vblank as short as NTSC's by adding 51 idle lines after the picture instead.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(format!(
+                "Unknown region '{name}', expected ntsc, pal or dendy"
+            )),
+        }
+    }
+}
+
+impl Region {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        }
+    }
+
+    pub fn cpu_clock(&self) -> u32 {
+        match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        }
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal | Region::Dendy => 50.0070,
+        }
+    }
+
+    /// `frames_per_second` exactly, as a numerator and denominator: the CPU clock over the CPU
+    /// cycles in a frame, doubled since NTSC's average frame is 29780.5 cycles.
+    pub fn frame_rate(&self) -> (u32, u32) {
+        let doubled_cycles_per_frame = match self {
+            Region::Ntsc => 59_561,
+            Region::Pal => 66_495,
+            Region::Dendy => 70_928,
+        };
+        (self.cpu_clock() * 2, doubled_cycles_per_frame)
+    }
+
+    /// PPU dots per CPU cycle as a fraction, 3.2 on PAL.
+    pub fn ppu_dots_per_cpu_cycle(&self) -> (usize, usize) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+
+    /// Scanlines per frame, including the pre-render line.
+    pub fn scanlines(&self) -> u32 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    pub fn vblank_scanline(&self) -> u32 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Only the NTSC PPU skips a dot on odd frames while rendering.
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        *self == Region::Ntsc
+    }
+
+    /// The PAL PPU, and the Dendy's clone of it, swap the red and green emphasis bits of PPUMASK.
+    pub fn swaps_red_green_emphasis(&self) -> bool {
+        *self != Region::Ntsc
+    }
+
+    /// CPU cycles between frame counter steps.
+    pub fn frame_counter_period(&self) -> usize {
+        match self {
+            Region::Ntsc | Region::Dendy => 7457,
+            Region::Pal => 8313,
+        }
+    }
+
+    pub fn noise_periods(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_NOISE_PERIOD,
+            Region::Pal => &PAL_NOISE_PERIOD,
+        }
+    }
+
+    pub fn dmc_rates(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_DMC_RATE,
+            Region::Pal => &PAL_DMC_RATE,
+        }
+    }
+}
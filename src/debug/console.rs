@@ -0,0 +1,21 @@
+use std::{
+    io::{stdin, BufRead},
+    sync::mpsc::{channel, Receiver},
+    thread,
+};
+
+/// Reads debugger commands from stdin on a background thread so the event loop never blocks.
+pub fn spawn_console() -> Receiver<String> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
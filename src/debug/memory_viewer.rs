@@ -0,0 +1,378 @@
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+use crate::core::{Bus, Nes};
+
+const BYTES_PER_ROW: usize = 16;
+const DEFAULT_DUMP_LENGTH: usize = 0x100;
+const HIGHLIGHT_FRAMES: u32 = 60;
+const MAX_LISTED_RESULTS: usize = 32;
+
+const WRITTEN_COLOR: &str = "\x1b[1;33m";
+const FROZEN_COLOR: &str = "\x1b[1;36m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+const HELP: &str = "\
+dump <cpu|ppu> <address> [length]   Hex dump, recent writes in yellow, frozen bytes in cyan
+poke <cpu|ppu> <address> <byte>...  Write bytes through the bus
+freeze <cpu|ppu> <address> <byte>   Rewrite a byte every frame
+unfreeze <cpu|ppu> <address>|all    Stop freezing a byte
+search new <cpu|ppu>                Start a search over the whole address space
+search <eq|ne|gt|lt> <byte>         Keep addresses compared against a value
+search <changed|unchanged|increased|decreased>
+                                    Keep addresses compared against the last search
+search list                         Show the remaining addresses";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AddressSpace {
+    Cpu,
+    Ppu,
+}
+
+impl AddressSpace {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "cpu" => Ok(AddressSpace::Cpu),
+            "ppu" => Ok(AddressSpace::Ppu),
+            _ => Err(format!(
+                "Unknown address space '{name}', expected cpu or ppu"
+            )),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            AddressSpace::Cpu => 0x10000,
+            AddressSpace::Ppu => 0x4000,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            AddressSpace::Cpu => 0,
+            AddressSpace::Ppu => 1,
+        }
+    }
+
+    fn bus<'a>(&self, nes: &'a Nes) -> &'a Rc<RefCell<Bus>> {
+        match self {
+            AddressSpace::Cpu => nes.cpu_bus(),
+            AddressSpace::Ppu => nes.ppu_bus(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Freeze {
+    space: AddressSpace,
+    address: u16,
+    value: u8,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum SearchFilter {
+    Equal(u8),
+    NotEqual(u8),
+    Greater(u8),
+    Less(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl SearchFilter {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            SearchFilter::Equal(value) => current == value,
+            SearchFilter::NotEqual(value) => current != value,
+            SearchFilter::Greater(value) => current > value,
+            SearchFilter::Less(value) => current < value,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous,
+        }
+    }
+}
+
+struct Search {
+    space: AddressSpace,
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+}
+
+/// Hex viewer and editor over the CPU and PPU buses, driven by text commands.
+pub struct MemoryViewer {
+    frame: u32,
+    last_written: [Vec<u32>; 2],
+    freezes: Vec<Freeze>,
+    search: Option<Search>,
+}
+
+impl MemoryViewer {
+    pub fn new(nes: &Nes) -> Self {
+        nes.cpu_bus().borrow_mut().set_write_tracking(true);
+        nes.ppu_bus().borrow_mut().set_write_tracking(true);
+
+        Self {
+            frame: 0,
+            last_written: [
+                vec![0; AddressSpace::Cpu.size()],
+                vec![0; AddressSpace::Ppu.size()],
+            ],
+            freezes: Vec::new(),
+            search: None,
+        }
+    }
+
+    /// Records the writes made during the last frame and reapplies frozen values.
+    pub fn update(&mut self, nes: &Nes) {
+        self.frame += 1;
+
+        for space in [AddressSpace::Cpu, AddressSpace::Ppu] {
+            let writes = space.bus(nes).borrow_mut().take_writes();
+            let last_written = &mut self.last_written[space.index()];
+            for address in writes {
+                last_written[address as usize % space.size()] = self.frame;
+            }
+        }
+
+        for freeze in &self.freezes {
+            freeze
+                .space
+                .bus(nes)
+                .borrow_mut()
+                .write_byte(freeze.address, freeze.value);
+        }
+        if !self.freezes.is_empty() {
+            nes.cpu_bus().borrow_mut().take_writes();
+            nes.ppu_bus().borrow_mut().take_writes();
+        }
+    }
+
+    pub fn execute(&mut self, nes: &Nes, command: &str) -> String {
+        let arguments: Vec<&str> = command.split_whitespace().collect();
+        self.run(nes, &arguments).unwrap_or_else(|e| e)
+    }
+
+    fn run(&mut self, nes: &Nes, arguments: &[&str]) -> Result<String, String> {
+        match arguments {
+            [] => Ok(String::new()),
+            ["help"] => Ok(HELP.into()),
+            ["dump", space, address] => self.dump(
+                nes,
+                AddressSpace::parse(space)?,
+                parse_address(address)?,
+                None,
+            ),
+            ["dump", space, address, length] => self.dump(
+                nes,
+                AddressSpace::parse(space)?,
+                parse_address(address)?,
+                Some(parse_address(length)? as usize),
+            ),
+            ["poke", space, address, values @ ..] if !values.is_empty() => self.poke(
+                nes,
+                AddressSpace::parse(space)?,
+                parse_address(address)?,
+                values,
+            ),
+            ["freeze", space, address, value] => self.freeze(
+                AddressSpace::parse(space)?,
+                parse_address(address)?,
+                parse_byte(value)?,
+            ),
+            ["unfreeze", "all"] => {
+                self.freezes.clear();
+                Ok("Removed all freezes".into())
+            }
+            ["unfreeze", space, address] => {
+                self.unfreeze(AddressSpace::parse(space)?, parse_address(address)?)
+            }
+            ["search", "new", space] => self.new_search(nes, AddressSpace::parse(space)?),
+            ["search", "list"] => self.list_search(nes),
+            ["search", filter @ ..] => self.filter_search(nes, parse_filter(filter)?),
+            _ => Err(format!(
+                "Unknown command '{}', try 'help'",
+                arguments.join(" ")
+            )),
+        }
+    }
+
+    fn dump(
+        &self,
+        nes: &Nes,
+        space: AddressSpace,
+        address: u16,
+        length: Option<usize>,
+    ) -> Result<String, String> {
+        let bus = space.bus(nes).borrow();
+        let address = address as usize % space.size();
+        let start = address & !(BYTES_PER_ROW - 1);
+        let end = (address + length.unwrap_or(DEFAULT_DUMP_LENGTH)).min(space.size());
+
+        let mut output = String::new();
+        for row in (start..end).step_by(BYTES_PER_ROW) {
+            let _ = write!(output, "${row:04X}:");
+            let mut text = String::with_capacity(BYTES_PER_ROW);
+            for address in row..(row + BYTES_PER_ROW) {
+                let value = bus.peek_byte(address as u16);
+                let color = if self.is_frozen(space, address as u16) {
+                    Some(FROZEN_COLOR)
+                } else if self.was_recently_written(space, address) {
+                    Some(WRITTEN_COLOR)
+                } else {
+                    None
+                };
+                match color {
+                    Some(color) => {
+                        let _ = write!(output, " {color}{value:02X}{RESET_COLOR}");
+                    }
+                    None => {
+                        let _ = write!(output, " {value:02X}");
+                    }
+                }
+                text.push(if value.is_ascii_graphic() {
+                    value as char
+                } else {
+                    '.'
+                });
+            }
+            let _ = writeln!(output, "  |{text}|");
+        }
+
+        Ok(output)
+    }
+
+    fn poke(
+        &self,
+        nes: &Nes,
+        space: AddressSpace,
+        address: u16,
+        values: &[&str],
+    ) -> Result<String, String> {
+        let values = values
+            .iter()
+            .map(|value| parse_byte(value))
+            .collect::<Result<Vec<u8>, String>>()?;
+
+        let mut bus = space.bus(nes).borrow_mut();
+        for (offset, &value) in values.iter().enumerate() {
+            bus.write_byte(address.wrapping_add(offset as u16), value);
+        }
+
+        Ok(format!("Wrote {} byte(s) at ${address:04X}", values.len()))
+    }
+
+    fn freeze(&mut self, space: AddressSpace, address: u16, value: u8) -> Result<String, String> {
+        self.freezes
+            .retain(|f| !(f.space == space && f.address == address));
+        self.freezes.push(Freeze {
+            space,
+            address,
+            value,
+        });
+        Ok(format!("Froze ${address:04X} to ${value:02X}"))
+    }
+
+    fn unfreeze(&mut self, space: AddressSpace, address: u16) -> Result<String, String> {
+        let count = self.freezes.len();
+        self.freezes
+            .retain(|f| !(f.space == space && f.address == address));
+        if self.freezes.len() == count {
+            Err(format!("${address:04X} is not frozen"))
+        } else {
+            Ok(format!("Unfroze ${address:04X}"))
+        }
+    }
+
+    fn new_search(&mut self, nes: &Nes, space: AddressSpace) -> Result<String, String> {
+        let snapshot = snapshot(nes, space);
+        self.search = Some(Search {
+            space,
+            candidates: (0..space.size()).map(|a| a as u16).collect(),
+            snapshot,
+        });
+        Ok(format!("Searching {} addresses", space.size()))
+    }
+
+    fn filter_search(&mut self, nes: &Nes, filter: SearchFilter) -> Result<String, String> {
+        let Some(search) = &mut self.search else {
+            return Err("No search in progress, start one with 'search new <cpu|ppu>'".into());
+        };
+
+        let current = snapshot(nes, search.space);
+        search.candidates.retain(|&address| {
+            filter.matches(search.snapshot[address as usize], current[address as usize])
+        });
+        search.snapshot = current;
+
+        Ok(format!("{} address(es) remaining", search.candidates.len()))
+    }
+
+    fn list_search(&self, nes: &Nes) -> Result<String, String> {
+        let Some(search) = &self.search else {
+            return Err("No search in progress".into());
+        };
+
+        let bus = search.space.bus(nes).borrow();
+        let mut output = String::new();
+        for &address in search.candidates.iter().take(MAX_LISTED_RESULTS) {
+            let _ = writeln!(output, "${address:04X}: {:02X}", bus.peek_byte(address));
+        }
+        if search.candidates.len() > MAX_LISTED_RESULTS {
+            let _ = writeln!(
+                output,
+                "... and {} more",
+                search.candidates.len() - MAX_LISTED_RESULTS
+            );
+        }
+
+        Ok(output)
+    }
+
+    fn is_frozen(&self, space: AddressSpace, address: u16) -> bool {
+        self.freezes
+            .iter()
+            .any(|f| f.space == space && f.address == address)
+    }
+
+    fn was_recently_written(&self, space: AddressSpace, address: usize) -> bool {
+        let written = self.last_written[space.index()][address];
+        written > 0 && self.frame - written < HIGHLIGHT_FRAMES
+    }
+}
+
+fn snapshot(nes: &Nes, space: AddressSpace) -> Vec<u8> {
+    let bus = space.bus(nes).borrow();
+    (0..space.size())
+        .map(|address| bus.peek_byte(address as u16))
+        .collect()
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text
+        .trim_start_matches('$')
+        .trim_start_matches("0x")
+        .trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid hex address '{text}'"))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let value = parse_address(text)?;
+    u8::try_from(value).map_err(|_| format!("'{text}' does not fit in a byte"))
+}
+
+fn parse_filter(arguments: &[&str]) -> Result<SearchFilter, String> {
+    match arguments {
+        ["eq", value] => Ok(SearchFilter::Equal(parse_byte(value)?)),
+        ["ne", value] => Ok(SearchFilter::NotEqual(parse_byte(value)?)),
+        ["gt", value] => Ok(SearchFilter::Greater(parse_byte(value)?)),
+        ["lt", value] => Ok(SearchFilter::Less(parse_byte(value)?)),
+        ["changed"] => Ok(SearchFilter::Changed),
+        ["unchanged"] => Ok(SearchFilter::Unchanged),
+        ["increased"] => Ok(SearchFilter::Increased),
+        ["decreased"] => Ok(SearchFilter::Decreased),
+        _ => Err(format!("Unknown search filter '{}'", arguments.join(" "))),
+    }
+}
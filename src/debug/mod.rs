@@ -0,0 +1,8 @@
+mod console;
+pub use console::*;
+
+mod memory_viewer;
+pub use memory_viewer::*;
+
+mod ppu_viewer;
+pub use ppu_viewer::*;
@@ -0,0 +1,48 @@
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{core::PpuView, image::save_png};
+
+pub fn save_ppu_view(view: &PpuView, directory: &Path, name: &str) -> Result<(), String> {
+    if let Err(e) = fs::create_dir_all(directory) {
+        return Err(format!("Unable to create {}: {e}", directory.display()));
+    }
+
+    for (suffix, image) in [
+        ("nametables", &view.nametables),
+        ("patterns", &view.pattern_tables),
+        ("palette", &view.palette),
+        ("sprites", &view.sprites),
+    ] {
+        save_png(
+            &directory.join(format!("{name}_{suffix}.png")),
+            image.width,
+            image.height,
+            &image.pixels,
+        )?;
+    }
+
+    let mut oam = format!(
+        "Captured at scanline {}, pattern palette {}\n",
+        view.scanline, view.pattern_palette
+    );
+    for (i, entry) in view.oam.iter().enumerate() {
+        let priority = if entry.attributes & 0x20 > 0 {
+            "back"
+        } else {
+            "front"
+        };
+        let flip_horizontal = if entry.attributes & 0x40 > 0 { "H" } else { "" };
+        let flip_vertical = if entry.attributes & 0x80 > 0 { "V" } else { "" };
+        let _ = writeln!(
+            oam,
+            "#{i:02} x:{:3} y:{:3} tile:${:02X} palette:{} priority:{priority} flip:{flip_horizontal}{flip_vertical}",
+            entry.x,
+            entry.y,
+            entry.tile_index,
+            entry.attributes & 3,
+        );
+    }
+
+    let oam_path = directory.join(format!("{name}_oam.txt"));
+    fs::write(&oam_path, oam).map_err(|e| format!("Unable to write {}: {e}", oam_path.display()))
+}
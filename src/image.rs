@@ -0,0 +1,68 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::core::Image;
+
+pub fn save_png(path: &Path, width: usize, height: usize, pixels: &[u32]) -> Result<(), String> {
+    let file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Unable to create {}: {e}", path.display())),
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(bytemuck::cast_slice(&pixels[..width * height]))
+        .map_err(|e| e.to_string())
+}
+
+/// What a screenshot captures.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ScreenshotMode {
+    /// The whole 256x240 picture.
+    Full,
+    /// Only the part shown in the window, with the overscan cropped.
+    #[default]
+    Cropped,
+    /// The picture as drawn in the window, after shaders, at its size on screen.
+    Filtered,
+}
+
+impl FromStr for ScreenshotMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "full" => Ok(ScreenshotMode::Full),
+            "cropped" => Ok(ScreenshotMode::Cropped),
+            "filtered" => Ok(ScreenshotMode::Filtered),
+            _ => Err(format!(
+                "Unknown screenshot mode '{name}', expected full, cropped or filtered"
+            )),
+        }
+    }
+}
+
+/// Writes `image` to `directory` as `<name>_<milliseconds since the epoch>.png`, returning the
+/// path.
+pub fn save_screenshot(directory: &Path, name: &str, image: &Image) -> Result<PathBuf, String> {
+    if let Err(e) = fs::create_dir_all(directory) {
+        return Err(format!("Unable to create {}: {e}", directory.display()));
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = directory.join(format!("{name}_{timestamp}.png"));
+    save_png(&path, image.width, image.height, &image.pixels)?;
+    Ok(path)
+}
@@ -1,4 +1,13 @@
 pub mod audio;
+pub mod config;
 pub mod core;
+pub mod debug;
+pub mod image;
+pub mod movie;
+pub mod netplay;
+pub mod ntsc;
+pub mod pacer;
+pub mod recording;
+pub mod rewind;
 pub mod rom;
 pub mod window;
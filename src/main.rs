@@ -1,8 +1,28 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
 use clap::Parser;
 use gilrs::{EventType, Gilrs};
 use rnes::{
-    core::Nes,
-    window::{MainWindow, NATIVE_RESOLUTION},
+    audio::{open_sink, AudioDriver, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE},
+    config::Config,
+    core::{Channel, Image, InputDeviceKind, Nes, Palette, PaletteAdjustments, Region},
+    debug::{save_ppu_view, spawn_console, MemoryViewer},
+    image::{save_screenshot, ScreenshotMode},
+    movie::Movie,
+    netplay::Netplay,
+    ntsc::{NtscFilter, NTSC_OUTPUT_WIDTH},
+    pacer::FramePacer,
+    recording::{AudioRecorder, Recorder, RecordingFormat},
+    rewind::RewindBuffer,
+    window::{MainWindow, ShaderPreset, BUILTIN_PRESETS, NATIVE_RESOLUTION},
 };
 use winit::{
     event::*,
@@ -15,9 +35,99 @@ struct Args {
     #[arg(short, long)]
     rom: String,
     #[arg(long)]
+    config: Option<PathBuf>,
+    #[arg(long)]
+    region: Option<Region>,
+    #[arg(long)]
+    palette: Option<String>,
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    palette_hue: f32,
+    #[arg(long, default_value_t = 1.0)]
+    palette_saturation: f32,
+    #[arg(long, default_value_t = 1.0)]
+    palette_contrast: f32,
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    palette_brightness: f32,
+    #[arg(long)]
+    ntsc_filter: bool,
+    #[arg(long)]
+    shader: Option<String>,
+    #[arg(long)]
     show_ops: bool,
     #[arg(long)]
     show_header: bool,
+    #[arg(long, default_value_t = 240)]
+    ppu_view_scanline: u32,
+    #[arg(long, default_value_t = 0)]
+    ppu_view_palette: u8,
+    #[arg(long, default_value = "ppu_view")]
+    ppu_view_dir: PathBuf,
+    #[arg(long, default_value = "screenshots")]
+    screenshot_dir: PathBuf,
+    #[arg(long, default_value = "cropped")]
+    screenshot_mode: ScreenshotMode,
+    #[arg(long)]
+    record_video: bool,
+    #[arg(long, default_value = "avi")]
+    video_format: RecordingFormat,
+    #[arg(long, default_value = "recordings")]
+    video_dir: PathBuf,
+    #[arg(long)]
+    record_audio: bool,
+    #[arg(long, default_value_t = 48000)]
+    audio_sample_rate: u32,
+    #[arg(
+        long,
+        help = "Record a WAV per APU channel (pulse1, pulse2, triangle, noise, dmc) next to the \
+                mix. Cartridge expansion audio has no stem"
+    )]
+    audio_stems: bool,
+    #[arg(long, default_value = "recordings")]
+    audio_dir: PathBuf,
+    #[arg(long)]
+    audio_driver: Option<AudioDriver>,
+    #[arg(long)]
+    audio_output_rate: Option<u32>,
+    #[arg(long)]
+    audio_latency: Option<u32>,
+    #[arg(long, default_value = "audio.wav")]
+    audio_wav: PathBuf,
+    #[arg(long)]
+    volume: Option<f32>,
+    #[arg(long)]
+    mute: Vec<Channel>,
+    #[arg(long)]
+    solo: Vec<Channel>,
+    #[arg(long)]
+    debug_console: bool,
+    #[arg(long)]
+    cdl: Option<PathBuf>,
+    #[arg(long)]
+    port1: Option<InputDeviceKind>,
+    #[arg(long)]
+    port2: Option<InputDeviceKind>,
+    #[arg(long)]
+    record: Option<PathBuf>,
+    #[arg(long)]
+    record_from_state: Option<PathBuf>,
+    #[arg(long, conflicts_with = "record")]
+    play: Option<PathBuf>,
+    #[arg(long, requires = "netplay_bind", conflicts_with_all = ["record", "play"])]
+    netplay_peer: Option<SocketAddr>,
+    #[arg(long, requires = "netplay_peer")]
+    netplay_bind: Option<SocketAddr>,
+    #[arg(long, default_value_t = 1)]
+    netplay_player: usize,
+    #[arg(long, default_value_t = 2)]
+    netplay_delay: u32,
+    #[arg(long)]
+    rewind: bool,
+    #[arg(long, default_value_t = 2)]
+    rewind_interval: u32,
+    #[arg(long, default_value_t = 60)]
+    rewind_seconds: u32,
+    #[arg(long, default_value_t = 1)]
+    rewind_speed: u32,
 }
 
 #[tokio::main]
@@ -35,8 +145,177 @@ async fn main() {
         vec![0u32; NATIVE_RESOLUTION.width as usize * NATIVE_RESOLUTION.height as usize];
     let cli = Args::parse();
     let mut nes = Nes::new(&cli.rom, cli.show_ops, cli.show_header).unwrap();
+    if let Some(region) = cli.region {
+        nes.set_region(region);
+    }
+    let mut ntsc_filter = cli
+        .ntsc_filter
+        .then(|| NtscFilter::new(picture_adjustments(&cli)));
+    let mut filtered = vec![0u32; NTSC_OUTPUT_WIDTH as usize * NATIVE_RESOLUTION.height as usize];
+    match load_palette(&cli) {
+        Ok(Some(palette)) => nes.set_palette(palette),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    }
+    if let Some(cdl) = &cli.cdl {
+        let previous = fs::read(cdl).ok();
+        if let Err(e) = nes.start_code_data_log(previous.as_deref()) {
+            eprintln!("{e}");
+            return;
+        }
+    }
+
+    let [port1, port2] = nes.default_input_devices();
+    nes.set_input_devices([cli.port1.unwrap_or(port1), cli.port2.unwrap_or(port2)]);
+
+    let movie = if let Some(path) = &cli.play {
+        let movie = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read movie: {e}"))
+            .and_then(|text| Movie::from_fm2(&text))
+            .and_then(|mut movie| {
+                if !movie.matches_rom(&nes) {
+                    eprintln!("Warning: the movie was recorded with a different ROM");
+                }
+                movie.start_playback(&mut nes)?;
+                Ok(movie)
+            });
+        match movie {
+            Ok(movie) => Some(movie),
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        }
+    } else if cli.record.is_some() {
+        if let Some(state) = &cli.record_from_state {
+            let result = fs::read(state)
+                .map_err(|e| e.to_string())
+                .and_then(|state| nes.load_state(&state));
+            if let Err(e) = result {
+                eprintln!("Unable to load savestate: {e}");
+                return;
+            }
+        }
+        let rom_filename = Path::new(&cli.rom)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match Movie::record(&mut nes, &rom_filename, cli.record_from_state.is_none()) {
+            Ok(movie) => Some(movie),
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    let movie = movie.map(|movie| Rc::new(RefCell::new(movie)));
+    if let Some(movie) = &movie {
+        nes.set_input_provider(Some(movie.clone()));
+    }
+
+    let mut netplay = match (cli.netplay_bind, cli.netplay_peer) {
+        (Some(bind), Some(peer)) => {
+            let player = cli.netplay_player.saturating_sub(1);
+            match Netplay::connect(bind, peer, player, cli.netplay_delay) {
+                Ok(netplay) => {
+                    nes.set_input_devices([InputDeviceKind::Standard; 2]);
+                    nes.set_input_provider(Some(netplay.input_provider()));
+                    Some(netplay)
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let config = match cli.config.clone().or_else(Config::default_path) {
+        Some(path) => Config::load_or_create(&path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            Config::default()
+        }),
+        None => Config::default(),
+    };
+    nes.controllers
+        .borrow_mut()
+        .set_bindings(config.players.clone());
+    let hotkeys = config.hotkeys.clone();
+    let fast_forward_speed = config.fast_forward_speed;
+    let shader_parameters = config.video.shader_parameters.clone();
+    let shader = cli.shader.as_deref().unwrap_or(&config.video.shader);
+    if let Err(e) = set_shader(&mut window, shader, &shader_parameters) {
+        eprintln!("{e}");
+    }
+    window.set_overscan(config.video.overscan);
+    window.set_pixel_aspect(config.video.pixel_aspect);
+    window.set_integer_scaling(config.video.integer_scaling);
+    window.set_fullscreen(config.video.fullscreen);
+    if let Err(e) = open_audio(&mut nes, &cli, &config) {
+        eprintln!("{e}");
+        return;
+    }
+    set_audio_mix(&mut nes, &cli, &config);
+    // The shader hotkey cycles through the built-in presets from wherever this starts.
+    let mut shader_index = BUILTIN_PRESETS.iter().position(|name| *name == shader);
+    let savestate_path = PathBuf::from(&cli.rom).with_extension("state");
+    let rom_name = Path::new(&cli.rom)
+        .file_stem()
+        .map_or("rnes".into(), |stem| stem.to_string_lossy().into_owned());
+
+    let mut recordings = Recordings::default();
+    if cli.record_video {
+        recordings.toggle_video(&mut nes, &cli, &rom_name);
+    }
+    if cli.record_audio {
+        recordings.toggle_audio(&mut nes, &cli, &rom_name);
+    }
 
     let mut gamepad = Gilrs::new().unwrap();
+    for (id, _) in gamepad.gamepads() {
+        nes.controllers.borrow_mut().connect_gamepad(id);
+    }
+
+    let mut debugger = if cli.debug_console {
+        Some((spawn_console(), MemoryViewer::new(&nes)))
+    } else {
+        None
+    };
+
+    let frames_per_second = nes.region().frames_per_second();
+    // Movies and netplay only know how to move forward, so savestates and rewind are off while
+    // either runs.
+    let locked = movie.is_some() || netplay.is_some();
+    if cli.rewind && locked {
+        eprintln!("Rewind is disabled during movies and netplay");
+    }
+    let mut rewind_buffer = (cli.rewind && !locked).then(|| {
+        RewindBuffer::new(
+            cli.rewind_interval,
+            (cli.rewind_seconds as f64 * frames_per_second / cli.rewind_interval.max(1) as f64)
+                as usize,
+        )
+    });
+    let mut rewinding = false;
+    let mut paused = false;
+    let mut frame_advance = false;
+    let mut fast_forward = false;
+    // Slow motion steps through these speeds.
+    const SPEEDS: [f64; 3] = [1.0, 0.5, 0.25];
+    let mut speed_index = 0;
+    const VOLUME_STEP: f32 = 0.1;
+    let mut pacer = FramePacer::new(frames_per_second);
+    // A display within half a percent of the NES frame rate paces frames by itself through
+    // vsync; dynamic rate control in the audio output absorbs the difference.
+    let vsync_paced = window
+        .refresh_rate()
+        .is_some_and(|rate| (rate / frames_per_second - 1.0).abs() < 0.005);
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -44,25 +323,175 @@ async fn main() {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } => {
-                if window_id == window.window.id() {
-                    window.input(event, control_flow);
-                    if let WindowEvent::KeyboardInput {
-                        input:
-                            winit::event::KeyboardInput {
-                                virtual_keycode: Some(keycode),
-                                state,
-                                ..
-                            },
-                        ..
-                    } = &event
-                    {
-                        nes.controller.borrow_mut().input_keyboard(keycode, state);
+            } if window_id == window.window.id() => {
+                window.input(event, control_flow);
+                if let WindowEvent::KeyboardInput {
+                    input:
+                        winit::event::KeyboardInput {
+                            virtual_keycode: Some(keycode),
+                            state,
+                            ..
+                        },
+                    ..
+                } = &event
+                {
+                    let pressed = *state == ElementState::Pressed;
+                    let key = Some(*keycode);
+                    if key == hotkeys.rewind {
+                        rewinding = pressed;
+                    } else if key == hotkeys.fast_forward {
+                        fast_forward = pressed;
+                    } else if key == hotkeys.ppu_view {
+                        if pressed {
+                            nes.request_ppu_view(cli.ppu_view_scanline, cli.ppu_view_palette);
+                        }
+                    } else if key == hotkeys.shader {
+                        if pressed {
+                            let index =
+                                shader_index.map_or(0, |index| (index + 1) % BUILTIN_PRESETS.len());
+                            shader_index = Some(index);
+                            let name = BUILTIN_PRESETS[index];
+                            match set_shader(&mut window, name, &shader_parameters) {
+                                Ok(()) => println!("Shader: {name}"),
+                                Err(e) => eprintln!("{e}"),
+                            }
+                        }
+                    } else if key == hotkeys.fullscreen {
+                        if pressed {
+                            window.set_fullscreen(!window.fullscreen());
+                        }
+                    } else if key == hotkeys.pixel_aspect {
+                        if pressed {
+                            window.set_pixel_aspect(window.pixel_aspect().toggled());
+                        }
+                    } else if key == hotkeys.overscan {
+                        if pressed {
+                            let overscan = window.overscan().next_preset();
+                            window.set_overscan(overscan);
+                            println!(
+                                "Overscan: top {}, bottom {}, left {}, right {}",
+                                overscan.top, overscan.bottom, overscan.left, overscan.right
+                            );
+                        }
+                    } else if key == hotkeys.integer_scaling {
+                        if pressed {
+                            window.set_integer_scaling(!window.integer_scaling());
+                        }
+                    } else if key == hotkeys.screenshot {
+                        if pressed {
+                            let image = match cli.screenshot_mode {
+                                ScreenshotMode::Full => Ok(Image {
+                                    width: NATIVE_RESOLUTION.width as usize,
+                                    height: NATIVE_RESOLUTION.height as usize,
+                                    pixels: screen.clone(),
+                                }),
+                                ScreenshotMode::Cropped if ntsc_filter.is_some() => {
+                                    Ok(window.crop(&filtered, NTSC_OUTPUT_WIDTH))
+                                }
+                                ScreenshotMode::Cropped => {
+                                    Ok(window.crop(&screen, NATIVE_RESOLUTION.width))
+                                }
+                                ScreenshotMode::Filtered => window.capture(),
+                            };
+                            match image.and_then(|image| {
+                                save_screenshot(&cli.screenshot_dir, &rom_name, &image)
+                            }) {
+                                Ok(path) => println!("Saved {}", path.display()),
+                                Err(e) => eprintln!("{e}"),
+                            }
+                        }
+                    } else if key == hotkeys.record_video {
+                        if pressed {
+                            recordings.toggle_video(&mut nes, &cli, &rom_name);
+                        }
+                    } else if key == hotkeys.record_audio {
+                        if pressed {
+                            recordings.toggle_audio(&mut nes, &cli, &rom_name);
+                        }
+                    } else if key == hotkeys.volume_up || key == hotkeys.volume_down {
+                        if pressed {
+                            let step = if key == hotkeys.volume_up {
+                                VOLUME_STEP
+                            } else {
+                                -VOLUME_STEP
+                            };
+                            nes.set_volume(nes.volume() + step);
+                            println!("Volume: {:.0}%", nes.volume() * 100.0);
+                        }
+                    } else if key == hotkeys.save_state {
+                        if pressed {
+                            match fs::write(&savestate_path, nes.save_state()) {
+                                Ok(()) => {
+                                    if let Some(movie) = &movie {
+                                        movie.borrow_mut().savestate_made();
+                                    }
+                                }
+                                Err(e) => eprintln!("Unable to write savestate: {e}"),
+                            }
+                        }
+                    } else if key == hotkeys.load_state {
+                        if pressed && netplay.is_some() {
+                            eprintln!("Savestates cannot be loaded during netplay");
+                        } else if pressed {
+                            // Loading a state while recording a movie is a rerecord.
+                            let result = fs::read(&savestate_path)
+                                .map_err(|e| e.to_string())
+                                .and_then(|state| match &movie {
+                                    Some(movie) => movie.borrow_mut().rerecord(&mut nes, &state),
+                                    None => nes.load_state(&state),
+                                });
+                            if let Err(e) = result {
+                                eprintln!("Unable to load savestate: {e}");
+                            }
+                        }
+                    } else if key == hotkeys.reset {
+                        if pressed {
+                            nes.reset();
+                        }
+                    } else if key == hotkeys.pause {
+                        if pressed {
+                            paused = !paused;
+                        }
+                    } else if key == hotkeys.frame_advance {
+                        if pressed {
+                            paused = true;
+                            frame_advance = true;
+                        }
+                    } else if key == hotkeys.slow_motion {
+                        if pressed {
+                            speed_index = (speed_index + 1) % SPEEDS.len();
+                            println!("Speed: {:.0}%", SPEEDS[speed_index] * 100.0);
+                        }
+                    } else {
+                        nes.controllers.borrow_mut().input_keyboard(keycode, state);
                     }
                 }
+                match event {
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let target = window.screen_position(*position);
+                        nes.controllers.borrow_mut().aim(target);
+                    }
+                    WindowEvent::CursorLeft { .. } => nes.controllers.borrow_mut().aim(None),
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => nes
+                        .controllers
+                        .borrow_mut()
+                        .mouse_button(*state == ElementState::Pressed),
+                    _ => {}
+                }
             }
             Event::RedrawRequested(window_id) if window_id == window.window.id() => {
-                match window.render(&screen) {
+                let rendered = match &mut ntsc_filter {
+                    Some(filter) => {
+                        filter.apply(&nes.pixels(), nes.color_phase(), &mut filtered);
+                        window.render(&filtered, NTSC_OUTPUT_WIDTH)
+                    }
+                    None => window.render(&screen, NATIVE_RESOLUTION.width),
+                };
+                match rendered {
                     Ok(_) => {}
                     Err(e) => {
                         eprintln!("{e:?}");
@@ -70,26 +499,293 @@ async fn main() {
                 }
             }
             Event::MainEventsCleared => {
-                while let Some(gilrs::Event { event, .. }) = gamepad.next_event() {
+                while let Some(gilrs::Event { id, event, .. }) = gamepad.next_event() {
+                    let mut controllers = nes.controllers.borrow_mut();
                     match event {
+                        EventType::Connected => {
+                            controllers.connect_gamepad(id);
+                        }
+                        EventType::Disconnected => controllers.disconnect_gamepad(id),
                         EventType::ButtonPressed(button, ..) => {
-                            nes.controller.borrow_mut().gamepad_press(button)
+                            controllers.gamepad_press(id, button)
                         }
                         EventType::ButtonReleased(button, ..) => {
-                            nes.controller.borrow_mut().gamepad_release(button)
+                            controllers.gamepad_release(id, button)
+                        }
+                        EventType::AxisChanged(axis, value, ..) => {
+                            controllers.gamepad_axis(id, axis, value)
                         }
                         _ => {}
                     }
                 }
 
-                const CPU_CYCLES_PER_FRAME: usize = 29780;
-                if let Err(e) = nes.emulate(CPU_CYCLES_PER_FRAME, &mut screen, &window) {
-                    eprintln!("{e}");
-                    *control_flow = ControlFlow::Exit;
+                // While rewinding, each frame restores an older snapshot and then runs one
+                // silent frame from it so there is a picture to show.
+                let rewinding = rewinding && rewind_buffer.is_some();
+                if let (Some(rewind_buffer), true) = (&mut rewind_buffer, rewinding) {
+                    for _ in 0..cli.rewind_speed.max(1) {
+                        match rewind_buffer.step_back(&mut nes) {
+                            Ok(true) => {}
+                            Ok(false) => break,
+                            Err(e) => {
+                                eprintln!("{e}");
+                                break;
+                            }
+                        }
+                    }
+                }
+                nes.set_muted(rewinding);
+
+                if let Some(netplay) = &mut netplay {
+                    // Both sides have to run at the same pace, so speed controls are ignored.
+                    if !vsync_paced {
+                        pacer.wait(1.0);
+                    }
+                    // Frames replayed in a rollback are not recorded, so only the samples of the
+                    // last frame run are kept.
+                    let result = netplay.run_frame(&mut nes, |nes| {
+                        nes.take_captured_samples();
+                        nes.run_frame(&mut screen, &window)
+                    });
+                    match result {
+                        Ok(true) => recordings.record_frame(&mut nes, &screen),
+                        Ok(false) => {}
+                        Err(e) => {
+                            eprintln!("{e}");
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                } else {
+                    // Fast-forward without a speed limit runs as many frames as fit in one display
+                    // refresh. Otherwise one frame is run when the pacer says it is due, at the
+                    // fast-forward speed while it is held. Fast-forward has no audio.
+                    const FAST_FORWARD_SLICE: Duration = Duration::from_millis(16);
+                    let fast_forward = fast_forward && !rewinding;
+                    let unlimited = fast_forward && fast_forward_speed == 0;
+                    let speed = if fast_forward {
+                        fast_forward_speed as f64
+                    } else {
+                        SPEEDS[speed_index]
+                    };
+                    nes.set_audio_speed((!fast_forward).then_some(speed as f32));
+
+                    let mut run_frames = if paused && !rewinding {
+                        std::mem::take(&mut frame_advance)
+                    } else {
+                        let synced = vsync_paced && speed == 1.0;
+                        if !unlimited && !synced {
+                            pacer.wait(speed);
+                        }
+                        true
+                    };
+                    let slice_start = Instant::now();
+                    while run_frames {
+                        if let Err(e) = nes.run_frame(&mut screen, &window) {
+                            eprintln!("{e}");
+                            *control_flow = ControlFlow::Exit;
+                            break;
+                        }
+                        if let (Some(rewind_buffer), false) = (&mut rewind_buffer, rewinding) {
+                            rewind_buffer.record(&nes);
+                        }
+                        recordings.record_frame(&mut nes, &screen);
+                        run_frames = unlimited && slice_start.elapsed() < FAST_FORWARD_SLICE;
+                    }
+                    if unlimited {
+                        pacer.restart();
+                    }
+                }
+                if let Some((console, viewer)) = &mut debugger {
+                    viewer.update(&nes);
+                    while let Ok(command) = console.try_recv() {
+                        println!("{}", viewer.execute(&nes, &command));
+                    }
+                }
+                if let Some(view) = nes.take_ppu_view() {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis();
+                    if let Err(e) = save_ppu_view(&view, &cli.ppu_view_dir, &timestamp.to_string())
+                    {
+                        eprintln!("{e}");
+                    }
                 }
                 window.window.request_redraw();
             }
+            Event::LoopDestroyed => {
+                recordings.stop(&mut nes);
+                if let Err(e) = nes.finish_audio() {
+                    eprintln!("{e}");
+                }
+                if let (Some(path), Some(movie)) = (&cli.record, &movie) {
+                    if let Err(e) = fs::write(path, movie.borrow().to_fm2()) {
+                        eprintln!("Unable to write movie: {e}");
+                    }
+                }
+                if let (Some(cdl), Some(log)) = (&cli.cdl, nes.code_data_log()) {
+                    if let Err(e) = fs::write(cdl, log) {
+                        eprintln!("Unable to write CDL file: {e}");
+                    }
+                }
+            }
             _ => {}
         }
     });
 }
+
+// The config's audio output, with the command line's on top.
+fn open_audio(nes: &mut Nes, cli: &Args, config: &Config) -> Result<(), String> {
+    let driver = cli.audio_driver.unwrap_or(config.audio.driver);
+    let sample_rate = cli.audio_output_rate.unwrap_or(config.audio.sample_rate);
+    let latency = cli.audio_latency.unwrap_or(config.audio.latency);
+    if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+        return Err(format!(
+            "Sample rate {sample_rate} is outside {MIN_SAMPLE_RATE}-{MAX_SAMPLE_RATE} Hz"
+        ));
+    }
+
+    let sink = open_sink(driver, sample_rate, &cli.audio_wav);
+    nes.set_audio_sink(sink, Duration::from_millis(latency as u64));
+    Ok(())
+}
+
+// The config's volume and channel mix, with the command line's on top.
+fn set_audio_mix(nes: &mut Nes, cli: &Args, config: &Config) {
+    nes.set_volume(cli.volume.unwrap_or(config.audio.volume));
+    for channel in Channel::ALL {
+        let mut mix = config
+            .audio
+            .channels
+            .get(&channel)
+            .copied()
+            .unwrap_or_default();
+        mix.muted |= cli.mute.contains(&channel);
+        mix.solo |= cli.solo.contains(&channel);
+        nes.set_channel_mix(channel, mix);
+    }
+}
+
+// `--palette ntsc` generates the palette from the picture controls, anything else is a `.pal` file.
+fn load_palette(cli: &Args) -> Result<Option<Palette>, String> {
+    match cli.palette.as_deref() {
+        None => Ok(None),
+        Some("ntsc") => Ok(Some(Palette::ntsc(&picture_adjustments(cli)))),
+        Some(path) => {
+            let data = fs::read(path).map_err(|e| format!("Unable to read palette {path}: {e}"))?;
+            Palette::from_pal(&data).map(Some)
+        }
+    }
+}
+
+// The video and audio being recorded, which share the APU's sample capture.
+#[derive(Default)]
+struct Recordings {
+    video: Option<Recorder>,
+    audio: Option<AudioRecorder>,
+}
+
+impl Recordings {
+    fn toggle_video(&mut self, nes: &mut Nes, cli: &Args, rom_name: &str) {
+        if let Some(video) = self.video.take() {
+            finish_recording(video.path().to_path_buf(), video.finish());
+        } else {
+            let path = recording_path(&cli.video_dir, rom_name);
+            match Recorder::start(&path, cli.video_format, nes.region()) {
+                Ok(video) => {
+                    println!("Recording to {}", video.path().display());
+                    self.video = Some(video);
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+        self.update_capture(nes);
+    }
+
+    fn toggle_audio(&mut self, nes: &mut Nes, cli: &Args, rom_name: &str) {
+        if let Some(audio) = self.audio.take() {
+            finish_recording(audio.path().to_path_buf(), audio.finish());
+        } else {
+            let path = recording_path(&cli.audio_dir, rom_name);
+            let region = nes.region();
+            match AudioRecorder::start(&path, cli.audio_sample_rate, cli.audio_stems, region) {
+                Ok(audio) => {
+                    println!("Recording audio to {}", audio.path().display());
+                    self.audio = Some(audio);
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+        self.update_capture(nes);
+    }
+
+    fn record_frame(&mut self, nes: &mut Nes, screen: &[u32]) {
+        if self.video.is_none() && self.audio.is_none() {
+            return;
+        }
+        let samples = nes.take_captured_samples();
+        let video_result = self
+            .video
+            .as_mut()
+            .map_or(Ok(()), |video| video.record_frame(screen, &samples.mixed));
+        let audio_result = self
+            .audio
+            .as_mut()
+            .map_or(Ok(()), |audio| audio.record(&samples));
+        if let Err(e) = video_result.and(audio_result) {
+            eprintln!("{e}");
+            self.stop(nes);
+        }
+    }
+
+    fn stop(&mut self, nes: &mut Nes) {
+        if let Some(video) = self.video.take() {
+            finish_recording(video.path().to_path_buf(), video.finish());
+        }
+        if let Some(audio) = self.audio.take() {
+            finish_recording(audio.path().to_path_buf(), audio.finish());
+        }
+        self.update_capture(nes);
+    }
+
+    fn update_capture(&self, nes: &mut Nes) {
+        let stems = self.audio.as_ref().is_some_and(AudioRecorder::has_stems);
+        nes.set_sample_capture(self.video.is_some() || self.audio.is_some(), stems);
+    }
+}
+
+fn recording_path(directory: &Path, rom_name: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    directory.join(format!("{rom_name}_{timestamp}"))
+}
+
+fn finish_recording(path: PathBuf, result: Result<(), String>) {
+    match result {
+        Ok(()) => println!("Saved {}", path.display()),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+fn set_shader(
+    window: &mut MainWindow,
+    name: &str,
+    parameters: &BTreeMap<String, f32>,
+) -> Result<(), String> {
+    window.set_shader_preset(&ShaderPreset::open(name)?)?;
+    for (parameter, value) in parameters {
+        window.set_shader_parameter(parameter, *value);
+    }
+    Ok(())
+}
+
+fn picture_adjustments(cli: &Args) -> PaletteAdjustments {
+    PaletteAdjustments {
+        hue: cli.palette_hue,
+        saturation: cli.palette_saturation,
+        contrast: cli.palette_contrast,
+        brightness: cli.palette_brightness,
+    }
+}
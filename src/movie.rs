@@ -0,0 +1,355 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::core::{InputDeviceKind, InputFrame, InputProvider, Nes, Region, MAX_PLAYERS};
+
+const FM2_VERSION: u32 = 3;
+// Button letters as they appear in an FM2 input field, from bit 7 down to bit 0.
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MovieMode {
+    Recording,
+    Playing,
+    Finished,
+}
+
+/// Per-frame controller input and console commands in the FCEUX `.fm2` format.
+///
+/// A movie starts either from power-on or from an embedded savestate. Savestates written by
+/// FCEUX cannot be loaded, so only power-on movies are portable between the two emulators.
+pub struct Movie {
+    rom_filename: String,
+    rom_checksum: [u8; 16],
+    guid: String,
+    rerecord_count: u32,
+    pal: bool,
+    four_score: bool,
+    ports: [bool; 2],
+    comments: Vec<String>,
+    savestate: Option<Vec<u8>>,
+    frames: Vec<InputFrame>,
+    mode: MovieMode,
+    position: usize,
+    // How long the recording was when the last savestate was made, where a rerecord goes back to.
+    savestate_position: Option<usize>,
+}
+
+impl Movie {
+    /// Starts recording from the console's current state, which is embedded in the movie
+    /// unless `from_power_on` is set, in which case the console is powered on first.
+    pub fn record(nes: &mut Nes, rom_filename: &str, from_power_on: bool) -> Result<Self, String> {
+        let (four_score, ports) = match nes.controllers.borrow().devices() {
+            [InputDeviceKind::FourScore, InputDeviceKind::FourScore] => (true, [false; 2]),
+            [port1, port2] => (
+                false,
+                [port_has_controller(port1)?, port_has_controller(port2)?],
+            ),
+        };
+
+        let savestate = if from_power_on {
+            nes.power_on()?;
+            None
+        } else {
+            Some(nes.save_state())
+        };
+
+        Ok(Self {
+            rom_filename: rom_filename.to_string(),
+            rom_checksum: nes.rom_checksum(),
+            guid: new_guid(),
+            rerecord_count: 0,
+            pal: nes.region() == Region::Pal,
+            four_score,
+            ports,
+            comments: Vec::new(),
+            savestate,
+            frames: Vec::new(),
+            mode: MovieMode::Recording,
+            position: 0,
+            savestate_position: None,
+        })
+    }
+
+    pub fn from_fm2(text: &str) -> Result<Self, String> {
+        let mut movie = Self {
+            rom_filename: String::new(),
+            rom_checksum: [0; 16],
+            guid: String::new(),
+            rerecord_count: 0,
+            pal: false,
+            four_score: false,
+            ports: [true, true],
+            comments: Vec::new(),
+            savestate: None,
+            frames: Vec::new(),
+            mode: MovieMode::Playing,
+            position: 0,
+            savestate_position: None,
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.starts_with('|') {
+                let frame = movie
+                    .parse_frame(line)
+                    .ok_or_else(|| format!("Invalid input on line {}", number + 1))?;
+                movie.frames.push(frame);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if value != FM2_VERSION.to_string() => {
+                    return Err(format!("Unsupported FM2 version {value}"));
+                }
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "romChecksum" => {
+                    let checksum = decode_base64(value)?;
+                    movie.rom_checksum = checksum
+                        .try_into()
+                        .map_err(|_| "Invalid ROM checksum in movie".to_string())?;
+                }
+                "guid" => movie.guid = value.to_string(),
+                "rerecordCount" => movie.rerecord_count = value.parse().unwrap_or(0),
+                "palFlag" => movie.pal = value == "1",
+                "fourscore" => movie.four_score = value == "1",
+                "port0" | "port1" => {
+                    let port = (key == "port1") as usize;
+                    movie.ports[port] = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err("Movies only support standard controllers".into()),
+                    };
+                }
+                "comment" => movie.comments.push(value.to_string()),
+                "savestate" => movie.savestate = Some(decode_base64(value)?),
+                _ => {}
+            }
+        }
+
+        Ok(movie)
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut text = format!(
+            "version {FM2_VERSION}\n\
+             emuVersion 22020\n\
+             rerecordCount {}\n\
+             palFlag {}\n\
+             romFilename {}\n\
+             romChecksum base64:{}\n\
+             guid {}\n\
+             fourscore {}\n\
+             microphone 0\n\
+             port0 {}\n\
+             port1 {}\n\
+             port2 0\n\
+             FDS 0\n\
+             NewPPU 0\n",
+            self.rerecord_count,
+            self.pal as u8,
+            self.rom_filename,
+            STANDARD.encode(self.rom_checksum),
+            self.guid,
+            self.four_score as u8,
+            self.ports[0] as u8,
+            self.ports[1] as u8,
+        );
+        for comment in &self.comments {
+            text += &format!("comment {comment}\n");
+        }
+        if let Some(savestate) = &self.savestate {
+            text += &format!("savestate base64:{}\n", STANDARD.encode(savestate));
+        }
+
+        for frame in &self.frames {
+            text += &format!("|{}|", frame.commands);
+            for player in self.fields() {
+                if let Some(player) = player {
+                    text += &format_buttons(frame.buttons[player]);
+                }
+                text.push('|');
+            }
+            text += "|\n";
+        }
+        text
+    }
+
+    /// Puts the console in the movie's starting state and plugs in the controllers it needs.
+    pub fn start_playback(&mut self, nes: &mut Nes) -> Result<(), String> {
+        let devices = if self.four_score {
+            [InputDeviceKind::FourScore; 2]
+        } else {
+            self.ports.map(|connected| {
+                if connected {
+                    InputDeviceKind::Standard
+                } else {
+                    InputDeviceKind::None
+                }
+            })
+        };
+        nes.set_input_devices(devices);
+        if self.pal {
+            nes.set_region(Region::Pal);
+        }
+
+        match &self.savestate {
+            Some(savestate) => nes
+                .load_state(savestate)
+                .map_err(|e| format!("Unable to load the movie's savestate: {e}"))?,
+            None => nes.power_on()?,
+        }
+        self.mode = if self.frames.is_empty() {
+            MovieMode::Finished
+        } else {
+            MovieMode::Playing
+        };
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Notes where in the recording a savestate was just made, for `rerecord`.
+    pub fn savestate_made(&mut self) {
+        if self.mode == MovieMode::Recording {
+            self.savestate_position = Some(self.frames.len());
+        }
+    }
+
+    /// Loads a savestate made during this recording, dropping the input recorded since and
+    /// counting a rerecord.
+    pub fn rerecord(&mut self, nes: &mut Nes, savestate: &[u8]) -> Result<(), String> {
+        if self.mode != MovieMode::Recording {
+            return Err("Savestates cannot be loaded while a movie is playing".into());
+        }
+        let Some(position) = self.savestate_position else {
+            return Err("Only savestates made during this recording can be loaded".into());
+        };
+        nes.load_state(savestate)?;
+        self.frames.truncate(position);
+        self.rerecord_count += 1;
+        Ok(())
+    }
+
+    /// Whether the movie was made with the ROM the console is running.
+    pub fn matches_rom(&self, nes: &Nes) -> bool {
+        self.rom_checksum == nes.rom_checksum()
+    }
+
+    pub fn mode(&self) -> MovieMode {
+        self.mode
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // The player in each input field of a frame; a port with nothing plugged in has an empty
+    // field.
+    fn fields(&self) -> Vec<Option<usize>> {
+        if self.four_score {
+            (0..MAX_PLAYERS).map(Some).collect()
+        } else {
+            (0..2)
+                .map(|port| self.ports[port].then_some(port))
+                .collect()
+        }
+    }
+
+    fn parse_frame(&self, line: &str) -> Option<InputFrame> {
+        let mut fields = line.split('|').skip(1);
+        let mut frame = InputFrame {
+            commands: fields.next()?.trim().parse().ok()?,
+            ..Default::default()
+        };
+        for player in self.fields() {
+            let field = fields.next()?;
+            if let Some(player) = player {
+                frame.buttons[player] = parse_buttons(field)?;
+            }
+        }
+        Some(frame)
+    }
+}
+
+impl InputProvider for Movie {
+    fn frame_input(&mut self, live: InputFrame) -> InputFrame {
+        match self.mode {
+            MovieMode::Recording => {
+                self.frames.push(live);
+                live
+            }
+            MovieMode::Playing => {
+                let frame = self.frames[self.position];
+                self.position += 1;
+                if self.position == self.frames.len() {
+                    self.mode = MovieMode::Finished;
+                }
+                frame
+            }
+            MovieMode::Finished => live,
+        }
+    }
+}
+
+fn port_has_controller(device: InputDeviceKind) -> Result<bool, String> {
+    match device {
+        InputDeviceKind::None => Ok(false),
+        InputDeviceKind::Standard => Ok(true),
+        _ => Err("Movies only support standard controllers and the Four Score".into()),
+    }
+}
+
+fn format_buttons(buttons: u8) -> String {
+    FM2_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(i, &letter)| {
+            if buttons & (0x80 >> i) > 0 {
+                letter as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+fn parse_buttons(field: &str) -> Option<u8> {
+    if field.len() != FM2_BUTTONS.len() {
+        return None;
+    }
+    Some(
+        field
+            .bytes()
+            .enumerate()
+            .filter(|&(_, letter)| letter != b'.' && letter != b' ')
+            .fold(0, |buttons, (i, _)| buttons | (0x80 >> i)),
+    )
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, String> {
+    let value = value.strip_prefix("base64:").unwrap_or(value);
+    STANDARD
+        .decode(value)
+        .map_err(|e| format!("Invalid base64 in movie: {e}"))
+}
+
+fn new_guid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let hex = format!(
+        "{:032X}",
+        nanos.wrapping_mul(0x9E37_79B9_7F4A_7C15_F39C_C060_5CED_C835)
+    );
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,291 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    net::{SocketAddr, UdpSocket},
+    rc::Rc,
+};
+
+use crate::core::{InputFrame, InputProvider, Nes};
+
+const PACKET_MAGIC: &[u8; 4] = b"RNNP";
+// How many frames the game may run ahead of the peer's confirmed input before it waits.
+const MAX_ROLLBACK: u32 = 8;
+const MAX_PACKET_SIZE: usize = 1024;
+
+/// One player's buttons and console commands for a frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct PlayerInput {
+    buttons: u8,
+    commands: u8,
+}
+
+struct Session {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    local_player: usize,
+    delay: u32,
+    // The frame the next `frame_input` call is for.
+    frame: u32,
+    // Set while frames are replayed after a rollback, so live input is not taken again.
+    replaying: bool,
+    local: BTreeMap<u32, PlayerInput>,
+    remote: BTreeMap<u32, PlayerInput>,
+    // What was assumed for the peer on frames that ran before their input arrived.
+    predicted: BTreeMap<u32, PlayerInput>,
+    // The newest frame from which every remote input is known.
+    remote_confirmed: Option<u32>,
+    // The newest frame the peer has confirmed from us.
+    acked: Option<u32>,
+    rollback_to: Option<u32>,
+}
+
+impl Session {
+    fn remote_input(&mut self, frame: u32) -> PlayerInput {
+        if let Some(input) = self.remote.get(&frame) {
+            return *input;
+        }
+        // Guess that the peer is still holding whatever they held last, without repeating any
+        // resets.
+        let guess = PlayerInput {
+            buttons: self
+                .remote
+                .range(..frame)
+                .next_back()
+                .map_or(0, |(_, input)| input.buttons),
+            commands: 0,
+        };
+        self.predicted.insert(frame, guess);
+        guess
+    }
+
+    fn receive(&mut self) {
+        let mut packet = [0; MAX_PACKET_SIZE];
+        while let Ok((size, from)) = self.socket.recv_from(&mut packet) {
+            if from == self.peer {
+                self.read_packet(&packet[..size]);
+            }
+        }
+    }
+
+    // A packet is the magic, the newest frame the sender has confirmed from us, then the
+    // sender's inputs for consecutive frames starting at `first_frame`.
+    fn read_packet(&mut self, packet: &[u8]) {
+        if packet.len() < 13 || &packet[0..4] != PACKET_MAGIC {
+            return;
+        }
+        let acked = u32::from_le_bytes(packet[4..8].try_into().unwrap());
+        let first_frame = u32::from_le_bytes(packet[8..12].try_into().unwrap());
+        if acked != u32::MAX {
+            self.acked = Some(self.acked.map_or(acked, |previous| previous.max(acked)));
+        }
+
+        for (offset, input) in packet[12..].chunks_exact(2).enumerate() {
+            let frame = first_frame + offset as u32;
+            let input = PlayerInput {
+                buttons: input[0],
+                commands: input[1],
+            };
+            if self.remote.insert(frame, input).is_some() {
+                continue;
+            }
+            if let Some(prediction) = self.predicted.remove(&frame) {
+                if prediction != input {
+                    self.rollback_to = Some(self.rollback_to.map_or(frame, |f| f.min(frame)));
+                }
+            }
+        }
+
+        let mut next = self.remote_confirmed.map_or(0, |frame| frame + 1);
+        while self.remote.contains_key(&next) {
+            self.remote_confirmed = Some(next);
+            next += 1;
+        }
+    }
+
+    fn send(&self) {
+        let first_frame = self.acked.map_or(0, |frame| frame + 1);
+        let mut packet = PACKET_MAGIC.to_vec();
+        packet.extend(self.remote_confirmed.unwrap_or(u32::MAX).to_le_bytes());
+        packet.extend(first_frame.to_le_bytes());
+        for (_, input) in self
+            .local
+            .range(first_frame..)
+            .take((MAX_PACKET_SIZE - 12) / 2)
+        {
+            packet.push(input.buttons);
+            packet.push(input.commands);
+        }
+        // Losing a packet is fine, the same inputs are sent again next frame until acked.
+        let _ = self.socket.send_to(&packet, self.peer);
+    }
+
+    fn prune(&mut self) {
+        let oldest = self.frame.saturating_sub(MAX_ROLLBACK + 1);
+        let acked = self.acked.map_or(0, |frame| frame + 1);
+        self.local = self.local.split_off(&oldest.min(acked));
+        self.remote = self.remote.split_off(&oldest);
+        self.predicted = self.predicted.split_off(&oldest);
+    }
+}
+
+impl InputProvider for Session {
+    fn frame_input(&mut self, live: InputFrame) -> InputFrame {
+        if !self.replaying {
+            // The local player always uses the player 1 controls, whichever port they are on.
+            self.local.insert(
+                self.frame + self.delay,
+                PlayerInput {
+                    buttons: live.buttons[0],
+                    commands: live.commands,
+                },
+            );
+        }
+
+        let local = self.local.get(&self.frame).copied().unwrap_or_default();
+        let remote = self.remote_input(self.frame);
+        let mut input = InputFrame {
+            commands: local.commands | remote.commands,
+            ..Default::default()
+        };
+        input.buttons[self.local_player] = local.buttons;
+        input.buttons[1 - self.local_player] = remote.buttons;
+        self.frame += 1;
+        input
+    }
+}
+
+/// Two players on separate machines, each running the same game and exchanging their inputs
+/// over UDP.
+///
+/// Local input is applied `delay` frames late to give it time to reach the peer. When the peer's
+/// input is late anyway, it is predicted, and once it arrives any mispredicted frames are rolled
+/// back to a savestate and replayed.
+pub struct Netplay {
+    session: Rc<RefCell<Session>>,
+    snapshots: VecDeque<(u32, Vec<u8>)>,
+}
+
+impl Netplay {
+    /// Listens on `bind` and plays against `peer`. `local_player` is 0 for player 1 or 1 for
+    /// player 2; the peer must take the other one and use the same delay.
+    pub fn connect(
+        bind: SocketAddr,
+        peer: SocketAddr,
+        local_player: usize,
+        delay: u32,
+    ) -> Result<Self, String> {
+        if local_player > 1 {
+            return Err("Netplay supports players 1 and 2".into());
+        }
+        let socket = UdpSocket::bind(bind).map_err(|e| format!("Unable to bind to {bind}: {e}"))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| format!("Unable to configure socket: {e}"))?;
+
+        // Both sides apply nothing for the first `delay` frames, so those are already known.
+        let known: BTreeMap<u32, PlayerInput> = (0..delay)
+            .map(|frame| (frame, PlayerInput::default()))
+            .collect();
+
+        Ok(Self {
+            session: Rc::new(RefCell::new(Session {
+                socket,
+                peer,
+                local_player,
+                delay,
+                frame: 0,
+                replaying: false,
+                local: known.clone(),
+                remote: known,
+                predicted: BTreeMap::new(),
+                remote_confirmed: delay.checked_sub(1),
+                acked: None,
+                rollback_to: None,
+            })),
+            snapshots: VecDeque::new(),
+        })
+    }
+
+    /// The input provider to install on the console for the whole session.
+    pub fn input_provider(&self) -> Rc<RefCell<dyn InputProvider>> {
+        self.session.clone()
+    }
+
+    /// Runs the next frame with `emulate`, first rolling back and replaying any frames that were
+    /// run with the wrong input for the peer. Returns false without running anything when the
+    /// peer has fallen too far behind.
+    pub fn run_frame(
+        &mut self,
+        nes: &mut Nes,
+        mut emulate: impl FnMut(&mut Nes) -> Result<(), String>,
+    ) -> Result<bool, String> {
+        let (rollback_to, current_frame) = {
+            let mut session = self.session.borrow_mut();
+            session.receive();
+            (session.rollback_to.take(), session.frame)
+        };
+
+        if let Some(rollback_to) = rollback_to {
+            self.rollback(nes, rollback_to, current_frame, &mut emulate)?;
+        }
+
+        let waiting = {
+            let session = self.session.borrow();
+            session.send();
+            let confirmed = session.remote_confirmed.map_or(0, |frame| frame + 1);
+            current_frame >= confirmed + MAX_ROLLBACK
+        };
+        if waiting {
+            return Ok(false);
+        }
+
+        self.snapshot(nes, current_frame);
+        emulate(nes)?;
+
+        let mut session = self.session.borrow_mut();
+        session.send();
+        session.prune();
+        Ok(true)
+    }
+
+    fn rollback(
+        &mut self,
+        nes: &mut Nes,
+        rollback_to: u32,
+        current_frame: u32,
+        emulate: &mut impl FnMut(&mut Nes) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let Some((_, state)) = self
+            .snapshots
+            .iter()
+            .find(|(frame, _)| *frame == rollback_to)
+        else {
+            return Err(format!(
+                "Netplay desynced: no savestate for frame {rollback_to}"
+            ));
+        };
+        nes.load_state(state)?;
+        self.snapshots.retain(|(frame, _)| *frame < rollback_to);
+
+        {
+            let mut session = self.session.borrow_mut();
+            session.frame = rollback_to;
+            session.replaying = true;
+        }
+        nes.set_muted(true);
+        let result = (rollback_to..current_frame).try_for_each(|frame| {
+            self.snapshot(nes, frame);
+            emulate(nes)
+        });
+        nes.set_muted(false);
+        self.session.borrow_mut().replaying = false;
+        result
+    }
+
+    fn snapshot(&mut self, nes: &Nes, frame: u32) {
+        self.snapshots.push_back((frame, nes.save_state()));
+        while self.snapshots.len() > MAX_ROLLBACK as usize + 1 {
+            self.snapshots.pop_front();
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    core::{composite_level, subcarrier_angle, yiq_to_color, PaletteAdjustments},
+    window::NATIVE_RESOLUTION,
+};
+
+// The PPU puts out 8 samples of composite signal per pixel, one per master clock.
+const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_OUTPUT_PIXEL: usize = 4;
+// Luma is averaged over one cycle of the color subcarrier, chroma over two, which is what blurs
+// color across neighbouring pixels.
+const LUMA_WINDOW: usize = 12;
+const CHROMA_WINDOW: usize = 24;
+const PHASES: usize = 12;
+const PIXEL_VALUES: usize = 512;
+const DOTS_PER_SCANLINE: usize = 341;
+
+/// Width of the filtered picture, twice the PPU's.
+pub const NTSC_OUTPUT_WIDTH: u32 = NATIVE_RESOLUTION.width * 2;
+
+/// Recreates the picture a television shows from the PPU's composite video, including the color
+/// fringing, artifacts and dot crawl that come from it. Only NTSC is modelled.
+pub struct NtscFilter {
+    adjustments: PaletteAdjustments,
+    // The signal for every pixel value at every subcarrier phase.
+    levels: Vec<[f32; PHASES]>,
+    // The demodulator's reference waves at every phase, with the hue control applied.
+    cos: [f32; PHASES],
+    sin: [f32; PHASES],
+    // Running sums of the signal over a scanline, as is and multiplied by each reference wave.
+    luma: Vec<f32>,
+    in_phase: Vec<f32>,
+    quadrature: Vec<f32>,
+}
+
+impl NtscFilter {
+    pub fn new(adjustments: PaletteAdjustments) -> Self {
+        let hue = adjustments.hue.to_radians();
+        let samples = NATIVE_RESOLUTION.width as usize * SAMPLES_PER_PIXEL;
+        Self {
+            adjustments,
+            levels: (0..PIXEL_VALUES as u16)
+                .map(|pixel| std::array::from_fn(|phase| composite_level(pixel, phase)))
+                .collect(),
+            cos: std::array::from_fn(|phase| (subcarrier_angle(phase) + hue).cos()),
+            sin: std::array::from_fn(|phase| (subcarrier_angle(phase) + hue).sin()),
+            luma: vec![0.0; samples + 1],
+            in_phase: vec![0.0; samples + 1],
+            quadrature: vec![0.0; samples + 1],
+        }
+    }
+
+    /// Filters a frame of 9-bit PPU pixels into `output`, which is `NTSC_OUTPUT_WIDTH` wide.
+    /// `color_phase` is the subcarrier phase at the frame's first pixel, as given by the console.
+    pub fn apply(&mut self, pixels: &[u16], color_phase: usize, output: &mut [u32]) {
+        let width = NATIVE_RESOLUTION.width as usize;
+        let output_width = NTSC_OUTPUT_WIDTH as usize;
+        for (row, (line, output_line)) in pixels
+            .chunks_exact(width)
+            .zip(output.chunks_exact_mut(output_width))
+            .enumerate()
+        {
+            let line_phase = color_phase + row * DOTS_PER_SCANLINE * SAMPLES_PER_PIXEL;
+            self.modulate(line, line_phase);
+
+            for (x, color) in output_line.iter_mut().enumerate() {
+                let center = x * SAMPLES_PER_OUTPUT_PIXEL + SAMPLES_PER_OUTPUT_PIXEL / 2;
+                let y = window_average(&self.luma, center, LUMA_WINDOW);
+                let i = window_average(&self.in_phase, center, CHROMA_WINDOW);
+                let q = window_average(&self.quadrature, center, CHROMA_WINDOW);
+                *color = yiq_to_color(y, i, q, &self.adjustments);
+            }
+        }
+    }
+
+    fn modulate(&mut self, line: &[u16], line_phase: usize) {
+        let (mut luma, mut in_phase, mut quadrature) = (0.0, 0.0, 0.0);
+        for (x, &pixel) in line.iter().enumerate() {
+            let levels = &self.levels[pixel as usize % PIXEL_VALUES];
+            for sample in 0..SAMPLES_PER_PIXEL {
+                let index = x * SAMPLES_PER_PIXEL + sample;
+                let phase = (line_phase + index) % PHASES;
+                let level = levels[phase];
+                luma += level;
+                in_phase += level * self.cos[phase];
+                quadrature += level * self.sin[phase];
+                self.luma[index + 1] = luma;
+                self.in_phase[index + 1] = in_phase;
+                self.quadrature[index + 1] = quadrature;
+            }
+        }
+    }
+}
+
+// The mean of the samples in a window centered on `center`, from a running sum. Samples past
+// the edges of the line are blanking, which is black.
+fn window_average(sums: &[f32], center: usize, window: usize) -> f32 {
+    let start = center.saturating_sub(window / 2);
+    let end = (center + window / 2).min(sums.len() - 1);
+    (sums[end] - sums[start]) / window as f32
+}
@@ -0,0 +1,39 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+// How far behind schedule the pacer may fall before it gives up catching up.
+const MAX_LAG_FRAMES: u32 = 4;
+
+/// Keeps emulated frames in step with the wall clock, at any speed.
+pub struct FramePacer {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(frames_per_second: f64) -> Self {
+        Self {
+            frame_duration: Duration::from_secs_f64(1.0 / frames_per_second),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Sleeps until the next frame is due at `speed` times normal speed.
+    pub fn wait(&mut self, speed: f64) {
+        let now = Instant::now();
+        if now < self.next_frame {
+            thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > self.frame_duration * MAX_LAG_FRAMES {
+            // After a stall, e.g. while paused, start over rather than rushing to catch up.
+            self.next_frame = now;
+        }
+        self.next_frame += self.frame_duration.div_f64(speed);
+    }
+
+    /// Forgets the schedule, for when frames ran without waiting.
+    pub fn restart(&mut self) {
+        self.next_frame = Instant::now();
+    }
+}
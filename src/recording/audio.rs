@@ -0,0 +1,104 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::Decimator;
+use crate::{
+    audio::{WavWriter, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE},
+    core::{CapturedSamples, Channel, Region},
+};
+
+/// Records the APU's audio to 16-bit mono WAV at any sample rate, optionally with a stem for
+/// each channel alongside the mix. Like `Recorder`, it follows the CPU clock rather than the
+/// sound card.
+pub struct AudioRecorder {
+    path: PathBuf,
+    mixed: Stream,
+    // One per channel in `Channel::ALL` order, if recording stems.
+    stems: Vec<Stream>,
+}
+
+struct Stream {
+    writer: WavWriter,
+    decimator: Decimator,
+}
+
+impl AudioRecorder {
+    /// Starts recording to `path` plus `.wav`, or with `stems` to a directory at `path` holding
+    /// `mix.wav` and a WAV named after each channel.
+    pub fn start(
+        path: &Path,
+        sample_rate: u32,
+        stems: bool,
+        region: Region,
+    ) -> Result<Self, String> {
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+            return Err(format!(
+                "Sample rate {sample_rate} is outside {MIN_SAMPLE_RATE}-{MAX_SAMPLE_RATE} Hz"
+            ));
+        }
+        let directory = if stems { Some(path) } else { path.parent() };
+        if let Some(directory) = directory {
+            fs::create_dir_all(directory)
+                .map_err(|e| format!("Unable to create {}: {e}", directory.display()))?;
+        }
+
+        let open = |path: &Path| -> Result<Stream, String> {
+            Ok(Stream {
+                writer: WavWriter::create(path, sample_rate, 1)?,
+                decimator: Decimator::new(region.cpu_clock(), sample_rate),
+            })
+        };
+        if stems {
+            Ok(Self {
+                path: path.to_path_buf(),
+                mixed: open(&path.join("mix.wav"))?,
+                stems: Channel::ALL
+                    .iter()
+                    .map(|channel| open(&path.join(format!("{}.wav", channel.name()))))
+                    .collect::<Result<_, _>>()?,
+            })
+        } else {
+            let path = path.with_extension("wav");
+            Ok(Self {
+                mixed: open(&path)?,
+                path,
+                stems: Vec::new(),
+            })
+        }
+    }
+
+    /// Where the recording is going, a directory if recording stems.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether `Nes::set_sample_capture` has to keep each channel's output.
+    pub fn has_stems(&self) -> bool {
+        !self.stems.is_empty()
+    }
+
+    /// Adds the samples taken from `Nes::take_captured_samples`.
+    pub fn record(&mut self, samples: &CapturedSamples) -> Result<(), String> {
+        self.mixed.write(samples.mixed.iter().copied())?;
+        for (index, stem) in self.stems.iter_mut().enumerate() {
+            stem.write(samples.stems.iter().map(|channels| channels[index]))?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        self.mixed.writer.finish()?;
+        self.stems
+            .into_iter()
+            .try_for_each(|stem| stem.writer.finish())
+    }
+}
+
+impl Stream {
+    fn write(&mut self, samples: impl ExactSizeIterator<Item = f32>) -> Result<(), String> {
+        let samples = self.decimator.decimate(samples);
+        self.writer.write_samples(&samples)
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIF_ISINTERLEAVED: u32 = 0x100;
+const AVIIF_KEYFRAME: u32 = 0x10;
+const BYTES_PER_SAMPLE: u32 = 2;
+// A chunk's ID and size, and an `idx1` entry.
+const CHUNK_HEADER_SIZE: u64 = 8;
+const INDEX_ENTRY_SIZE: u64 = 16;
+
+/// Writes uncompressed 24-bit video and 16-bit mono PCM to an AVI 1.0 file, one audio chunk per
+/// frame. Frame and sample counts in the header are filled in by `finish`.
+pub struct AviWriter {
+    file: BufWriter<File>,
+    width: usize,
+    height: usize,
+    // Where the fields only known at the end are in the header.
+    total_frames_position: u64,
+    video_length_position: u64,
+    audio_length_position: u64,
+    movi_position: u64,
+    // Bytes written after the `movi` list's type.
+    movi_size: u32,
+    index: Vec<u8>,
+    frames: u32,
+    samples: u32,
+}
+
+impl AviWriter {
+    /// `frame_rate` is frames per second as a numerator and denominator.
+    pub fn create(
+        path: &Path,
+        width: usize,
+        height: usize,
+        frame_rate: (u32, u32),
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+        let frame_size = (width * height * 3) as u32;
+        let (rate, scale) = frame_rate;
+
+        let mut header = Chunks::default();
+        header.fourcc(b"RIFF");
+        header.u32(0);
+        header.fourcc(b"AVI ");
+        let hdrl = header.begin_list(b"hdrl");
+
+        let avih = header.begin_chunk(b"avih");
+        header.u32((1_000_000 * scale as u64 / rate as u64) as u32);
+        header.u32(
+            (frame_size as u64 * rate as u64 / scale as u64) as u32
+                + sample_rate * BYTES_PER_SAMPLE,
+        );
+        header.u32(0);
+        header.u32(AVIF_HASINDEX | AVIF_ISINTERLEAVED);
+        let total_frames_position = header.position();
+        header.u32(0);
+        header.u32(0);
+        header.u32(2);
+        header.u32(frame_size);
+        header.u32(width as u32);
+        header.u32(height as u32);
+        header.zeros(16);
+        header.end(avih);
+
+        let video = header.begin_list(b"strl");
+        let strh = header.begin_chunk(b"strh");
+        header.fourcc(b"vids");
+        header.fourcc(b"DIB ");
+        header.zeros(12);
+        header.u32(scale);
+        header.u32(rate);
+        header.u32(0);
+        let video_length_position = header.position();
+        header.u32(0);
+        header.u32(frame_size);
+        header.u32(u32::MAX);
+        header.u32(0);
+        header.u16(0);
+        header.u16(0);
+        header.u16(width as u16);
+        header.u16(height as u16);
+        header.end(strh);
+        let strf = header.begin_chunk(b"strf");
+        header.u32(40);
+        header.u32(width as u32);
+        // Positive, so rows are stored bottom to top.
+        header.u32(height as u32);
+        header.u16(1);
+        header.u16(24);
+        header.u32(0);
+        header.u32(frame_size);
+        header.zeros(16);
+        header.end(strf);
+        header.end(video);
+
+        let audio = header.begin_list(b"strl");
+        let strh = header.begin_chunk(b"strh");
+        header.fourcc(b"auds");
+        header.zeros(16);
+        header.u32(BYTES_PER_SAMPLE);
+        header.u32(sample_rate * BYTES_PER_SAMPLE);
+        header.u32(0);
+        let audio_length_position = header.position();
+        header.u32(0);
+        header.u32(sample_rate * BYTES_PER_SAMPLE);
+        header.u32(u32::MAX);
+        header.u32(BYTES_PER_SAMPLE);
+        header.zeros(8);
+        header.end(strh);
+        let strf = header.begin_chunk(b"strf");
+        header.u16(1);
+        header.u16(1);
+        header.u32(sample_rate);
+        header.u32(sample_rate * BYTES_PER_SAMPLE);
+        header.u16(BYTES_PER_SAMPLE as u16);
+        header.u16(16);
+        header.u16(0);
+        header.end(strf);
+        header.end(audio);
+        header.end(hdrl);
+
+        header.fourcc(b"LIST");
+        header.u32(0);
+        let movi_position = header.position();
+        header.fourcc(b"movi");
+
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            width,
+            height,
+            total_frames_position,
+            video_length_position,
+            audio_length_position,
+            movi_position,
+            movi_size: 4,
+            index: Vec::new(),
+            frames: 0,
+            samples: 0,
+        };
+        writer.file.write_all(&header.bytes).map_err(write_error)?;
+        Ok(writer)
+    }
+
+    /// Bytes written so far, not counting the index.
+    pub fn size(&self) -> u64 {
+        self.movi_position + self.movi_size as u64
+    }
+
+    /// Adds a frame of RGBA pixels and the audio that plays during it. Fails without writing
+    /// anything if the file would pass the 4 GB an AVI 1.0 file can hold.
+    pub fn write_frame(&mut self, pixels: &[u32], samples: &[i16]) -> Result<(), String> {
+        let frame: Vec<u8> = pixels[..self.width * self.height]
+            .chunks_exact(self.width)
+            .rev()
+            .flatten()
+            .flat_map(|color| {
+                let [red, green, blue, _] = color.to_le_bytes();
+                [blue, green, red]
+            })
+            .collect();
+        let audio: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+        // The whole file, with the `idx1` chunk `finish` adds, has to fit the RIFF header's size.
+        let file_size = self.size()
+            + CHUNK_HEADER_SIZE
+            + self.index.len() as u64
+            + 2 * (CHUNK_HEADER_SIZE + INDEX_ENTRY_SIZE)
+            + (frame.len() + audio.len()) as u64;
+        if file_size > u32::MAX as u64 {
+            return Err("Unable to write video: the AVI file is full".into());
+        }
+
+        self.write_chunk(b"00db", &frame)?;
+        self.write_chunk(b"01wb", &audio)?;
+
+        self.frames += 1;
+        // Can't overflow, there are fewer samples than bytes in the file.
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let index = std::mem::take(&mut self.index);
+        // Everything after the RIFF header, which ends with the index chunk.
+        let riff_size = self.size() as u32 + index.len() as u32;
+        let (frames, samples, movi_size) = (self.frames, self.samples, self.movi_size);
+        let (total_frames, video_length) = (self.total_frames_position, self.video_length_position);
+        let (audio_length, movi) = (self.audio_length_position, self.movi_position);
+
+        self.file
+            .write_all(b"idx1")
+            .and_then(|_| self.file.write_all(&(index.len() as u32).to_le_bytes()))
+            .and_then(|_| self.file.write_all(&index))
+            .and_then(|_| self.patch(4, riff_size))
+            .and_then(|_| self.patch(total_frames, frames))
+            .and_then(|_| self.patch(video_length, frames))
+            .and_then(|_| self.patch(audio_length, samples))
+            .and_then(|_| self.patch(movi - 4, movi_size))
+            .and_then(|_| self.file.flush())
+            .map_err(write_error)
+    }
+
+    fn write_chunk(&mut self, id: &[u8; 4], data: &[u8]) -> Result<(), String> {
+        // Index offsets count from the `movi` list's type.
+        self.index.extend_from_slice(id);
+        self.index.extend_from_slice(&AVIIF_KEYFRAME.to_le_bytes());
+        self.index.extend_from_slice(&self.movi_size.to_le_bytes());
+        self.index
+            .extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        self.file
+            .write_all(id)
+            .and_then(|_| self.file.write_all(&(data.len() as u32).to_le_bytes()))
+            .and_then(|_| self.file.write_all(data))
+            .map_err(write_error)?;
+        self.movi_size += 8 + data.len() as u32;
+        Ok(())
+    }
+
+    fn patch(&mut self, position: u64, value: u32) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file.write_all(&value.to_le_bytes())
+    }
+}
+
+fn write_error(e: io::Error) -> String {
+    format!("Unable to write video: {e}")
+}
+
+// Builds the header, filling in each chunk's size when it ends.
+#[derive(Default)]
+struct Chunks {
+    bytes: Vec<u8>,
+}
+
+impl Chunks {
+    fn position(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    fn fourcc(&mut self, id: &[u8; 4]) {
+        self.bytes.extend_from_slice(id);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn zeros(&mut self, count: usize) {
+        self.bytes.resize(self.bytes.len() + count, 0);
+    }
+
+    // Returns where the chunk's data starts, for `end`.
+    fn begin_chunk(&mut self, id: &[u8; 4]) -> usize {
+        self.fourcc(id);
+        self.u32(0);
+        self.bytes.len()
+    }
+
+    fn begin_list(&mut self, list_type: &[u8; 4]) -> usize {
+        let start = self.begin_chunk(b"LIST");
+        self.fourcc(list_type);
+        start
+    }
+
+    fn end(&mut self, start: usize) {
+        let size = (self.bytes.len() - start) as u32;
+        self.bytes[start - 4..start].copy_from_slice(&size.to_le_bytes());
+    }
+}
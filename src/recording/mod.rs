@@ -0,0 +1,179 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+mod audio;
+mod avi;
+
+pub use audio::AudioRecorder;
+use avi::AviWriter;
+
+use crate::{audio::WavWriter, core::Region, image::save_png, window::NATIVE_RESOLUTION};
+
+/// Sample rate of recorded audio.
+pub const RECORDING_SAMPLE_RATE: u32 = 48000;
+// Past this an AVI 1.0 file is no longer safe to read, so the recording goes on in a new one.
+const MAX_AVI_SIZE: u64 = 2_000_000_000;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum RecordingFormat {
+    /// One uncompressed AVI, split into more every 2 GB.
+    #[default]
+    Avi,
+    /// A directory of numbered PNG frames and a WAV of the audio.
+    Png,
+}
+
+impl FromStr for RecordingFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "avi" => Ok(RecordingFormat::Avi),
+            "png" => Ok(RecordingFormat::Png),
+            _ => Err(format!(
+                "Unknown recording format '{name}', expected avi or png"
+            )),
+        }
+    }
+}
+
+enum Output {
+    Avi { writer: AviWriter, part: u32 },
+    Png { frame: u32, audio: WavWriter },
+}
+
+/// Records frames and the audio played during each of them. The audio follows the CPU clock
+/// exactly rather than the sound card, so it never drifts from the video, however fast or
+/// unevenly emulation ran.
+pub struct Recorder {
+    path: PathBuf,
+    output: Output,
+    frame_rate: (u32, u32),
+    decimator: Decimator,
+}
+
+impl Recorder {
+    /// Starts recording to `path` plus `.avi`, or to a directory at `path` for PNGs.
+    pub fn start(path: &Path, format: RecordingFormat, region: Region) -> Result<Self, String> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)
+                .map_err(|e| format!("Unable to create {}: {e}", directory.display()))?;
+        }
+
+        let frame_rate = region.frame_rate();
+        let (path, output) = match format {
+            RecordingFormat::Avi => {
+                let path = path.with_extension("avi");
+                let writer = create_avi(&path, frame_rate)?;
+                (path, Output::Avi { writer, part: 0 })
+            }
+            RecordingFormat::Png => {
+                fs::create_dir_all(path)
+                    .map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+                let audio = WavWriter::create(&path.join("audio.wav"), RECORDING_SAMPLE_RATE, 1)?;
+                (path.to_path_buf(), Output::Png { frame: 0, audio })
+            }
+        };
+
+        Ok(Self {
+            path,
+            output,
+            frame_rate,
+            decimator: Decimator::new(region.cpu_clock(), RECORDING_SAMPLE_RATE),
+        })
+    }
+
+    /// Where the recording is going, the first file for AVIs.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds a frame and the samples the APU mixed while it ran, as given by
+    /// `Nes::take_captured_samples`.
+    pub fn record_frame(&mut self, screen: &[u32], samples: &[f32]) -> Result<(), String> {
+        let samples = self.decimator.decimate(samples.iter().copied());
+        match &mut self.output {
+            Output::Avi { writer, part } => {
+                if writer.size() > MAX_AVI_SIZE {
+                    *part += 1;
+                    let path = self.path.with_extension(format!("{part}.avi"));
+                    let full = std::mem::replace(writer, create_avi(&path, self.frame_rate)?);
+                    full.finish()?;
+                }
+                writer.write_frame(screen, &samples)
+            }
+            Output::Png { frame, audio } => {
+                save_png(
+                    &self.path.join(format!("frame_{frame:06}.png")),
+                    NATIVE_RESOLUTION.width as usize,
+                    NATIVE_RESOLUTION.height as usize,
+                    screen,
+                )?;
+                *frame += 1;
+                audio.write_samples(&samples)
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self.output {
+            Output::Avi { writer, .. } => writer.finish(),
+            Output::Png { audio, .. } => audio.finish(),
+        }
+    }
+}
+
+fn create_avi(path: &Path, frame_rate: (u32, u32)) -> Result<AviWriter, String> {
+    AviWriter::create(
+        path,
+        NATIVE_RESOLUTION.width as usize,
+        NATIVE_RESOLUTION.height as usize,
+        frame_rate,
+        RECORDING_SAMPLE_RATE,
+    )
+}
+
+// Brings samples from the CPU clock down to the sample rate by averaging the ones each output
+// sample spans. The fraction of a sample left over at the end of a frame carries into the next,
+// so the total stays exact.
+struct Decimator {
+    clock_rate: u32,
+    sample_rate: u32,
+    phase: u32,
+    sum: f32,
+    count: u32,
+}
+
+impl Decimator {
+    fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            phase: 0,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn decimate(&mut self, samples: impl ExactSizeIterator<Item = f32>) -> Vec<i16> {
+        let mut output = Vec::with_capacity(
+            samples.len() * self.sample_rate as usize / self.clock_rate as usize + 1,
+        );
+        for sample in samples {
+            self.sum += sample;
+            self.count += 1;
+            self.phase += self.sample_rate;
+            if self.phase >= self.clock_rate {
+                self.phase -= self.clock_rate;
+                let average = self.sum / self.count as f32;
+                output.push((average.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+        output
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+
+use crate::core::Nes;
+
+/// Savestates captured every few frames, kept as deltas so long histories stay small.
+///
+/// Only the newest snapshot is stored in full. Each older snapshot is stored as the XOR against
+/// the snapshot after it, run-length encoded so the unchanged bytes cost almost nothing.
+pub struct RewindBuffer {
+    interval: u32,
+    capacity: usize,
+    frames_since_snapshot: u32,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    /// Captures a snapshot every `interval` frames, remembering at most `capacity` of them.
+    pub fn new(interval: u32, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames_since_snapshot: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Call once per emulated frame.
+    pub fn record(&mut self, nes: &Nes) {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let state = nes.save_state();
+        if let Some(previous) = self.latest.take() {
+            self.deltas.push_back(encode_delta(&previous, &state));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Restores the snapshot before the current one. Once the history is exhausted the oldest
+    /// snapshot is restored again and false is returned.
+    pub fn step_back(&mut self, nes: &mut Nes) -> Result<bool, String> {
+        let Some(latest) = &self.latest else {
+            return Ok(false);
+        };
+        self.frames_since_snapshot = 0;
+        let Some(delta) = self.deltas.pop_back() else {
+            nes.load_state(latest)?;
+            return Ok(false);
+        };
+
+        let previous = decode_delta(latest, &delta)?;
+        nes.load_state(&previous)?;
+        self.latest = Some(previous);
+
+        Ok(true)
+    }
+}
+
+/// Encodes `older` relative to `newer`: its length, then the XOR of the two where each run of
+/// zero bytes is written as a zero followed by the run length.
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(&(older.len() as u32).to_le_bytes());
+
+    let mut zero_run = 0u8;
+    for (i, &byte) in older.iter().enumerate() {
+        let difference = byte ^ newer.get(i).copied().unwrap_or(0);
+        if difference == 0 {
+            zero_run += 1;
+            if zero_run == u8::MAX {
+                delta.extend_from_slice(&[0, zero_run]);
+                zero_run = 0;
+            }
+            continue;
+        }
+        if zero_run > 0 {
+            delta.extend_from_slice(&[0, zero_run]);
+            zero_run = 0;
+        }
+        delta.push(difference);
+    }
+    if zero_run > 0 {
+        delta.extend_from_slice(&[0, zero_run]);
+    }
+
+    delta
+}
+
+// Fails if the delta doesn't decode to the length it was encoded with, i.e. it is truncated or
+// was made against a different snapshot.
+fn decode_delta(newer: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+    let (length, encoded) = delta
+        .split_first_chunk::<4>()
+        .ok_or("Rewind snapshot is truncated")?;
+    let length = u32::from_le_bytes(*length) as usize;
+    let mut older = Vec::with_capacity(length);
+
+    let mut encoded = encoded.iter();
+    while let Some(&difference) = encoded.next() {
+        if difference == 0 {
+            let run = encoded.next().copied().unwrap_or(0);
+            for _ in 0..run {
+                older.push(newer.get(older.len()).copied().unwrap_or(0));
+            }
+        } else {
+            older.push(newer.get(older.len()).copied().unwrap_or(0) ^ difference);
+        }
+    }
+
+    if older.len() != length {
+        return Err(format!(
+            "Rewind snapshot decoded to {} bytes, expected {length}",
+            older.len()
+        ));
+    }
+    Ok(older)
+}
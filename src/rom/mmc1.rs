@@ -1,9 +1,9 @@
 use bitfield::bitfield;
 use std::{cell::RefCell, rc::Rc};
 
-use crate::core::{Addressable, Bus, VRam};
+use crate::core::{Addressable, Bus, Savestate, StateReader, StateWriter, VRam};
 
-use super::MirrorArrangement;
+use super::{Cartridge, MirrorArrangement};
 
 const SHIFT_REGISTER_INITIAL: u8 = 0x10;
 const PRG_RAM_SIZE: usize = 8 * 1024;
@@ -31,7 +31,12 @@ pub struct Mmc1 {
     prg_ram: [u8; PRG_RAM_SIZE],
     prg_banks: Vec<[u8; PRG_ROM_SIZE]>,
     chr_banks: Vec<[u8; CHR_ROM_SIZE]>,
+    chr_ram: bool,
     vram: Rc<RefCell<VRam>>,
+    // Cycles left during which the serial port ignores writes. The MMC1 only takes the first of
+    // two writes on consecutive cycles, such as the dummy write of a read-modify-write
+    // instruction. That always ends with a read, so it is not saved.
+    write_cooldown: u8,
 }
 
 impl Mmc1 {
@@ -42,7 +47,7 @@ impl Mmc1 {
         bus: &Rc<RefCell<Bus>>,
         vram_bus: &Rc<RefCell<Bus>>,
         vram: &Rc<RefCell<VRam>>,
-    ) {
+    ) -> Rc<RefCell<Self>> {
         let mut cursor = 0;
         let mut prg_banks: Vec<[u8; PRG_ROM_SIZE]> = Vec::with_capacity(num_rom_banks as usize);
         for _ in 0..num_rom_banks {
@@ -69,55 +74,88 @@ impl Mmc1 {
             prg_ram: [0; PRG_RAM_SIZE],
             prg_banks,
             chr_banks,
+            chr_ram: num_chr_banks == 0,
             vram: vram.clone(),
+            write_cooldown: 0,
         }));
 
         bus.borrow_mut()
             .register_region(0x6000..=0xFFFF, rom.clone());
-        vram_bus.borrow_mut().register_region(0..=0x1FFF, rom);
+        vram_bus
+            .borrow_mut()
+            .register_region(0..=0x1FFF, rom.clone());
+
+        rom
+    }
+
+    /// Index of the 16KB PRG bank mapped at a CPU address in $8000-$FFFF.
+    fn prg_bank(&self, address: u16) -> usize {
+        match (address, self.control.prg_mode()) {
+            (0x8000..=0xBFFF, 0 | 1) => (self.prg_bank_switch & 0xFE) as usize,
+            (0x8000..=0xBFFF, 2) => 0,
+            (0x8000..=0xBFFF, 3) => self.prg_bank_switch as usize,
+            (_, 0 | 1) => (self.prg_bank_switch & 0xFE) as usize + 1,
+            (_, 2) => self.prg_bank_switch as usize,
+            (_, 3) => self.prg_banks.len() - 1,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Index of the 4KB CHR bank mapped at a PPU address in $0000-$1FFF.
+    fn chr_bank(&self, address: u16) -> usize {
+        match address {
+            0..=0xFFF => self.chr_bank0_switch as usize,
+            _ if self.control.chr_mode() => self.chr_bank1_switch as usize,
+            _ => self.chr_bank0_switch as usize + 1,
+        }
     }
 }
 
-impl Addressable for Mmc1 {
-    fn read_byte(&mut self, address: u16) -> Option<u8> {
+impl Cartridge for Mmc1 {
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
         match address {
-            0..=0xFFF => Some(self.chr_banks[self.chr_bank0_switch as usize][address as usize]),
-            0x1000..=0x1FFF => {
-                if self.control.chr_mode() {
-                    Some(self.chr_banks[self.chr_bank1_switch as usize][address as usize - 0x1000])
-                } else {
-                    Some(
-                        self.chr_banks[self.chr_bank0_switch as usize + 1]
-                            [address as usize - 0x1000],
-                    )
-                }
+            0x8000..=0xFFFF => {
+                Some(self.prg_bank(address) * PRG_ROM_SIZE + (address as usize & 0x3FFF))
             }
-            0x6000..=0x7FFF => Some(self.prg_ram[address as usize - 0x6000]),
-            0x8000..=0xBFFF => match self.control.prg_mode() {
-                0 | 1 => Some(
-                    self.prg_banks[(self.prg_bank_switch & 0xFE) as usize]
-                        [address as usize - 0x8000],
-                ),
-                2 => Some(self.prg_banks[0][address as usize - 0x8000]),
-                3 => Some(self.prg_banks[self.prg_bank_switch as usize][address as usize - 0x8000]),
-                _ => unreachable!(),
-            },
-            0xC000..=0xFFFF => match self.control.prg_mode() {
-                0 | 1 => Some(
-                    self.prg_banks[(self.prg_bank_switch & 0xFE) as usize + 1]
-                        [address as usize - 0xC000],
-                ),
-                2 => Some(self.prg_banks[self.prg_bank_switch as usize][address as usize - 0xC000]),
-                3 => Some(self.prg_banks[self.prg_banks.len() - 1][address as usize - 0xC000]),
-                _ => unreachable!(),
-            },
-            _ => {
-                println!("(warn) Unexpected read in MMC1 {address:X}");
-                None
+            _ => None,
+        }
+    }
+
+    fn chr_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0..=0x1FFF if !self.chr_ram => {
+                Some(self.chr_bank(address) * CHR_ROM_SIZE + (address as usize & 0xFFF))
             }
+            _ => None,
         }
     }
 
+    fn prg_rom_size(&self) -> usize {
+        self.prg_banks.len() * PRG_ROM_SIZE
+    }
+
+    fn chr_rom_size(&self) -> usize {
+        if self.chr_ram {
+            0
+        } else {
+            self.chr_banks.len() * CHR_ROM_SIZE
+        }
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.write_cooldown = self.write_cooldown.saturating_sub(1);
+    }
+}
+
+impl Addressable for Mmc1 {
+    fn read_byte(&mut self, address: u16) -> Option<u8> {
+        let data = self.peek(address);
+        if data.is_none() {
+            println!("(warn) Unexpected read in MMC1 {address:X}");
+        }
+        data
+    }
+
     fn write_byte(&mut self, address: u16, data: u8) {
         match address {
             0..=0xFFF => self.chr_banks[self.chr_bank0_switch as usize][address as usize] = data,
@@ -131,7 +169,9 @@ impl Addressable for Mmc1 {
                 }
             }
             0x6000..=0x7FFF => self.prg_ram[address as usize - 0x6000] = data,
+            0x8000..=0xFFFF if self.write_cooldown > 0 => self.write_cooldown = 2,
             0x8000..=0xFFFF => {
+                self.write_cooldown = 2;
                 if data & 0x80 > 0 {
                     self.control.set_prg_mode(3);
                     self.sr = SHIFT_REGISTER_INITIAL;
@@ -171,4 +211,47 @@ impl Addressable for Mmc1 {
             }
         }
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0..=0x1FFF => Some(self.chr_banks[self.chr_bank(address)][address as usize & 0xFFF]),
+            0x6000..=0x7FFF => Some(self.prg_ram[address as usize - 0x6000]),
+            0x8000..=0xFFFF => {
+                Some(self.prg_banks[self.prg_bank(address)][address as usize & 0x3FFF])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Savestate for Mmc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.prg_bank_switch);
+        state.write_u8(self.chr_bank0_switch);
+        state.write_u8(self.chr_bank1_switch);
+        state.write_u8(self.sr);
+        state.write_u8(self.control.0);
+        state.write_bytes(&self.prg_ram);
+        if self.chr_ram {
+            for bank in &self.chr_banks {
+                state.write_bytes(bank);
+            }
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.prg_bank_switch = state.read_u8()?;
+        self.chr_bank0_switch = state.read_u8()?;
+        self.chr_bank1_switch = state.read_u8()?;
+        self.sr = state.read_u8()?;
+        self.control.0 = state.read_u8()?;
+        self.write_cooldown = 0;
+        state.read_bytes(&mut self.prg_ram)?;
+        if self.chr_ram {
+            for bank in &mut self.chr_banks {
+                state.read_bytes(bank)?;
+            }
+        }
+        Ok(())
+    }
 }
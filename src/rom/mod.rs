@@ -4,9 +4,22 @@ pub use nrom::*;
 mod mmc1;
 pub use mmc1::*;
 
-use crate::core::{Bus, VRam};
+use crate::core::{Addressable, Bus, Region, Savestate, VRam};
 use std::{cell::RefCell, rc::Rc};
 
+/// A cartridge mapper, exposing how its current banking maps bus addresses back into the ROM.
+pub trait Cartridge: Addressable + Savestate {
+    /// Offset into PRG ROM of the byte mapped at a CPU address, if it is mapped to PRG ROM.
+    fn prg_rom_offset(&self, address: u16) -> Option<usize>;
+    /// Offset into CHR ROM of the byte mapped at a PPU address. Always `None` for CHR RAM.
+    fn chr_rom_offset(&self, address: u16) -> Option<usize>;
+    fn prg_rom_size(&self) -> usize;
+    fn chr_rom_size(&self) -> usize;
+
+    /// Called at the start of every CPU cycle, before that cycle's bus access.
+    fn cpu_cycle(&mut self) {}
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum MirrorArrangement {
     OneScreenLower,
@@ -38,6 +51,10 @@ pub struct RomHeader {
     chr: u8,
     pub mirroring: MirrorArrangement,
     pub mapper: Mapper,
+    /// NES 2.0 only: the input device the game expects, see `InputDeviceKind`.
+    pub default_expansion_device: Option<u8>,
+    /// The TV system, if the header names one.
+    pub region: Option<Region>,
 }
 
 impl RomHeader {
@@ -46,6 +63,7 @@ impl RomHeader {
             return Err("The ROM does not contain a valid iNES header.".into());
         }
 
+        let nes2 = header[7] & 0x0C == 0x08;
         Ok(Self {
             prg: header[4],
             chr: header[5],
@@ -54,6 +72,16 @@ impl RomHeader {
                 false => MirrorArrangement::Horizontal,
             },
             mapper: Mapper::from_id((header[6] >> 4) | (header[7] & 0xF0u8)),
+            default_expansion_device: nes2.then_some(header[15] & 0x3F),
+            // NES 2.0 uses byte 12, where 2 means the game runs on any region. iNES only has a
+            // PAL flag, which few dumps set.
+            region: match (nes2, header[12] & 0x03, header[9] & 0x01) {
+                (true, 0, _) => Some(Region::Ntsc),
+                (true, 1, _) => Some(Region::Pal),
+                (true, 3, _) => Some(Region::Dendy),
+                (false, _, 1) => Some(Region::Pal),
+                _ => None,
+            },
         })
     }
 }
@@ -64,7 +92,7 @@ pub fn load_rom(
     vram_bus: &Rc<RefCell<Bus>>,
     show_header: bool,
     vram: &Rc<RefCell<VRam>>,
-) -> Result<(), String> {
+) -> Result<(RomHeader, Rc<RefCell<dyn Cartridge>>), String> {
     let header = RomHeader::from_slice(&rom[0..16])?;
 
     vram.borrow_mut().set_mirroring(header.mirroring);
@@ -73,11 +101,11 @@ pub fn load_rom(
         println!("{header:?}");
     }
 
-    match header.mapper {
+    let cartridge: Rc<RefCell<dyn Cartridge>> = match header.mapper {
         Mapper::Nrom => Nrom::register(&rom[16..], header.prg, header.chr, bus, vram_bus),
         Mapper::Mmc1 => Mmc1::register(&rom[16..], header.prg, header.chr, bus, vram_bus, vram),
         _ => return Err("Unsupported mapper".into()),
-    }
+    };
 
-    Ok(())
+    Ok((header, cartridge))
 }
@@ -1,6 +1,8 @@
-use crate::core::{Addressable, Bus};
+use crate::core::{Addressable, Bus, Savestate, StateReader, StateWriter};
 use std::{cell::RefCell, rc::Rc};
 
+use super::Cartridge;
+
 const PRG_RAM_SIZE: usize = 0x800;
 const PRG_ROM_SIZE: usize = 0x4000;
 const CHR_ROM_SIZE: usize = 0x2000;
@@ -13,6 +15,8 @@ pub struct Nrom {
     prg_ram: [u8; PRG_RAM_SIZE],
     prg_rom: Vec<u8>,
     rom_banks: u8,
+    chr: Rc<RefCell<NromChr>>,
+    chr_rom_size: usize,
 }
 
 impl Nrom {
@@ -22,45 +26,71 @@ impl Nrom {
         chr_banks: u8,
         bus: &Rc<RefCell<Bus>>,
         vram_bus: &Rc<RefCell<Bus>>,
-    ) {
+    ) -> Rc<RefCell<Self>> {
+        let chr = if chr_banks > 0 {
+            NromChr {
+                chr_rom: data[(PRG_ROM_SIZE * rom_banks as usize)
+                    ..(PRG_ROM_SIZE * rom_banks as usize + CHR_ROM_SIZE)]
+                    .try_into()
+                    .unwrap(),
+            }
+        } else {
+            NromChr {
+                chr_rom: [0u8; CHR_ROM_SIZE],
+            }
+        };
+        let chr = Rc::new(RefCell::new(chr));
+        vram_bus
+            .borrow_mut()
+            .register_region(0..=0x1FFF, chr.clone());
+
         let rom = Rc::new(RefCell::new(Self {
             prg_ram: [0; PRG_RAM_SIZE],
             prg_rom: data[0..PRG_ROM_SIZE * rom_banks as usize].into(),
             rom_banks,
+            chr,
+            chr_rom_size: if chr_banks > 0 { CHR_ROM_SIZE } else { 0 },
         }));
-        bus.borrow_mut().register_region(0x6000..=0xFFFF, rom);
+        bus.borrow_mut()
+            .register_region(0x6000..=0xFFFF, rom.clone());
 
-        if chr_banks > 0 {
-            let chr = Rc::new(RefCell::new(NromChr {
-                chr_rom: data[(PRG_ROM_SIZE * rom_banks as usize)
-                    ..(PRG_ROM_SIZE * rom_banks as usize + CHR_ROM_SIZE)]
-                    .try_into()
-                    .unwrap(),
-            }));
-            vram_bus.borrow_mut().register_region(0x0..=0x1FFF, chr);
-        } else {
-            vram_bus.borrow_mut().register_region(
-                0..=0x1FFF,
-                Rc::new(RefCell::new(NromChr {
-                    chr_rom: [0u8; CHR_ROM_SIZE],
-                })),
-            );
-        }
+        rom
     }
 }
 
 impl Addressable for NromChr {
     fn read_byte(&mut self, address: u16) -> Option<u8> {
-        Some(self.chr_rom[address as usize])
+        self.peek(address)
     }
 
     fn write_byte(&mut self, address: u16, data: u8) {
         self.chr_rom[address as usize] = data;
     }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        Some(self.chr_rom[address as usize])
+    }
 }
 
 impl Addressable for Nrom {
     fn read_byte(&mut self, address: u16) -> Option<u8> {
+        let data = self.peek(address);
+        if data.is_none() {
+            eprintln!("(warn) NROM read address 0x{address:X} that was unexpected.");
+        }
+        data
+    }
+
+    fn write_byte(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram[(address as usize - 0x6000) % PRG_RAM_SIZE] = data,
+            _ => {
+                eprintln!("(warn) NROM write address 0x{address:X} that is not writable.");
+            }
+        }
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
         match address {
             0x6000..=0x7FFF => Some(self.prg_ram[(address as usize - 0x6000) % PRG_RAM_SIZE]),
             0x8000..=0xBFFF => Some(self.prg_rom[address as usize - 0x8000]),
@@ -68,22 +98,52 @@ impl Addressable for Nrom {
                 if self.rom_banks > 1 {
                     Some(self.prg_rom[address as usize - 0x8000])
                 } else {
-                    self.read_byte(address - PRG_ROM_SIZE as u16)
+                    self.peek(address - PRG_ROM_SIZE as u16)
                 }
             }
-            _ => {
-                eprintln!("(warn) NROM read address 0x{address:X} that was unexpected.");
-                None
-            }
+            _ => None,
         }
     }
+}
 
-    fn write_byte(&mut self, address: u16, data: u8) {
+impl Cartridge for Nrom {
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
         match address {
-            0x6000..=0x7FFF => self.prg_ram[(address as usize - 0x6000) % PRG_RAM_SIZE] = data,
-            _ => {
-                eprintln!("(warn) NROM write address 0x{address:X} that is not writable.");
-            }
+            0x8000..=0xFFFF => Some((address as usize - 0x8000) % self.prg_rom.len()),
+            _ => None,
+        }
+    }
+
+    fn chr_rom_offset(&self, address: u16) -> Option<usize> {
+        if (address as usize) < self.chr_rom_size {
+            Some(address as usize)
+        } else {
+            None
+        }
+    }
+
+    fn prg_rom_size(&self) -> usize {
+        self.prg_rom.len()
+    }
+
+    fn chr_rom_size(&self) -> usize {
+        self.chr_rom_size
+    }
+}
+
+impl Savestate for Nrom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.prg_ram);
+        if self.chr_rom_size == 0 {
+            state.write_bytes(&self.chr.borrow().chr_rom);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.prg_ram)?;
+        if self.chr_rom_size == 0 {
+            state.read_bytes(&mut self.chr.borrow_mut().chr_rom)?;
         }
+        Ok(())
     }
 }
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use super::NATIVE_RESOLUTION;
+
+/// The most that can be cropped from one edge.
+pub const MAX_OVERSCAN: u32 = 64;
+
+/// NES pixels hidden at each edge of the picture, the way a television's bezel covers them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overscan {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Default for Overscan {
+    fn default() -> Self {
+        Self {
+            top: 8,
+            bottom: 8,
+            left: 0,
+            right: 0,
+        }
+    }
+}
+
+impl Overscan {
+    /// What the overscan hotkey steps through: nothing cropped, the default, then 8 pixels off
+    /// every edge.
+    pub const PRESETS: [Overscan; 3] = [
+        Overscan {
+            top: 0,
+            bottom: 0,
+            left: 0,
+            right: 0,
+        },
+        Overscan {
+            top: 8,
+            bottom: 8,
+            left: 0,
+            right: 0,
+        },
+        Overscan {
+            top: 8,
+            bottom: 8,
+            left: 8,
+            right: 8,
+        },
+    ];
+
+    /// The preset after this one, or the first if this isn't one of them.
+    pub fn next_preset(&self) -> Self {
+        let index = Self::PRESETS.iter().position(|preset| preset == self);
+        Self::PRESETS[index.map_or(0, |index| (index + 1) % Self::PRESETS.len())]
+    }
+
+    pub fn clamped(self) -> Self {
+        Self {
+            top: self.top.min(MAX_OVERSCAN),
+            bottom: self.bottom.min(MAX_OVERSCAN),
+            left: self.left.min(MAX_OVERSCAN),
+            right: self.right.min(MAX_OVERSCAN),
+        }
+    }
+
+    /// Width and height of what is left of the picture, in NES pixels.
+    pub fn visible_size(&self) -> (u32, u32) {
+        (
+            NATIVE_RESOLUTION.width - self.left - self.right,
+            NATIVE_RESOLUTION.height - self.top - self.bottom,
+        )
+    }
+}
+
+/// The shape of the NES's pixels on screen.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PixelAspect {
+    #[default]
+    #[serde(rename = "square")]
+    Square,
+    /// Slightly wide pixels, as an NTSC console draws them on a 4:3 television.
+    #[serde(rename = "8:7")]
+    EightToSeven,
+}
+
+impl PixelAspect {
+    /// Width of a pixel relative to its height.
+    pub fn ratio(&self) -> f64 {
+        match self {
+            PixelAspect::Square => 1.0,
+            PixelAspect::EightToSeven => 8.0 / 7.0,
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            PixelAspect::Square => PixelAspect::EightToSeven,
+            PixelAspect::EightToSeven => PixelAspect::Square,
+        }
+    }
+}
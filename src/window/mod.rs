@@ -1,17 +1,24 @@
-use wgpu::{
-    util::DeviceExt, Buffer, Device, Queue, RenderPipeline, Surface, SurfaceConfiguration, Texture,
-};
+use std::sync::mpsc;
+
+use wgpu::{Device, Queue, Surface, SurfaceConfiguration, Texture, TextureFormat};
 use winit::{
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::WindowEvent,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{Fullscreen, Window, WindowBuilder},
 };
 
+use crate::core::Image;
+
+mod display;
+mod post_process;
+mod shader;
 mod uniform;
 mod vertex;
 
-use uniform::WindowUniform;
+pub use display::{Overscan, PixelAspect, MAX_OVERSCAN};
+use post_process::PostProcessChain;
+pub use shader::{ShaderFilter, ShaderPass, ShaderPreset, BUILTIN_PRESETS};
 
 pub const NATIVE_RESOLUTION: PhysicalSize<u32> = PhysicalSize::new(256, 240);
 pub const BYTES_PER_PIXEL: usize = 4;
@@ -22,12 +29,13 @@ pub struct MainWindow {
     surface: Surface,
     device: Device,
     queue: Queue,
-    render_pipeline: RenderPipeline,
-    vertex_buffer: Buffer,
-    uniform_buffer: Buffer,
-    uniform_bind_group: wgpu::BindGroup,
     screen_texture: Texture,
-    texture_bind_group: wgpu::BindGroup,
+    // Width of the frames passed to `render`, which the screen texture is cropped from.
+    frame_width: u32,
+    overscan: Overscan,
+    pixel_aspect: PixelAspect,
+    integer_scaling: bool,
+    post_process: PostProcessChain,
     config: SurfaceConfiguration,
 }
 
@@ -85,178 +93,231 @@ impl MainWindow {
             format: surface_format,
             width: window.inner_size().width,
             height: window.inner_size().height,
-            present_mode: surface_capabilities.present_modes[0],
+            // Fifo is always supported and waits for vertical blank.
+            present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: surface_capabilities.alpha_modes[0],
             view_formats: vec![],
         };
         surface.configure(&device, &config);
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Screen Vertices"),
-            contents: bytemuck::cast_slice(vertex::VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let overscan = Overscan::default();
+        let screen_texture = create_screen_texture(&device, overscan.visible_size());
+        let mut post_process = PostProcessChain::new(&device, config.format);
+        post_process.set_preset(&device, &ShaderPreset::default())?;
 
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Aspect Ratio Buffer"),
-            contents: bytemuck::cast_slice(&[WindowUniform::new(
-                NATIVE_RESOLUTION.width as f32 / NATIVE_RESOLUTION.height as f32,
-                window.inner_size().width as f32 / window.inner_size().height as f32,
-            )]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let mut main_window = Self {
+            window,
+            surface,
+            device,
+            queue,
+            screen_texture,
+            frame_width: NATIVE_RESOLUTION.width,
+            overscan,
+            pixel_aspect: PixelAspect::default(),
+            integer_scaling: false,
+            post_process,
+            config,
+        };
+        main_window.resize_post_process();
+        Ok(main_window)
+    }
 
-        let window_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: None,
-            });
+    /// Replaces the shader passes the picture goes through. The current ones are kept if the
+    /// preset's shaders do not compile.
+    pub fn set_shader_preset(&mut self, preset: &ShaderPreset) -> Result<(), String> {
+        self.post_process.set_preset(&self.device, preset)?;
+        self.resize_post_process();
+        Ok(())
+    }
 
-        let window_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &window_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
-            label: None,
-        });
+    /// Sets a `#parameter` of the current shaders. Returns false if none of them have it.
+    pub fn set_shader_parameter(&mut self, name: &str, value: f32) -> bool {
+        self.post_process.set_parameter(name, value)
+    }
 
-        let screen_texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: wgpu::Extent3d {
-                width: NATIVE_RESOLUTION.width,
-                height: NATIVE_RESOLUTION.height - 16,
-                depth_or_array_layers: 1,
-            },
+    pub fn overscan(&self) -> Overscan {
+        self.overscan
+    }
+
+    /// Crops the picture's edges, each by at most `MAX_OVERSCAN` pixels.
+    pub fn set_overscan(&mut self, overscan: Overscan) {
+        self.overscan = overscan.clamped();
+        self.recreate_screen_texture();
+    }
+
+    pub fn pixel_aspect(&self) -> PixelAspect {
+        self.pixel_aspect
+    }
+
+    pub fn set_pixel_aspect(&mut self, pixel_aspect: PixelAspect) {
+        self.pixel_aspect = pixel_aspect;
+        self.resize_post_process();
+    }
+
+    pub fn integer_scaling(&self) -> bool {
+        self.integer_scaling
+    }
+
+    /// Only scales the picture by whole numbers vertically, leaving a border around it. The
+    /// width follows the pixel aspect ratio, so it is only a whole multiple with square pixels.
+    pub fn set_integer_scaling(&mut self, integer_scaling: bool) {
+        self.integer_scaling = integer_scaling;
+        self.resize_post_process();
+    }
+
+    pub fn fullscreen(&self) -> bool {
+        self.window.fullscreen().is_some()
+    }
+
+    /// Switches between a window and borderless fullscreen on the current monitor.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.window
+            .set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+    }
+
+    /// The part of a frame `width` pixels wide that `render` shows, without the overscan.
+    pub fn crop(&self, frame: &[u32], width: u32) -> Image {
+        let (visible_width, visible_height) = self.overscan.visible_size();
+        let left = frame_columns(self.overscan.left, width) as usize;
+        let crop_width = frame_columns(visible_width, width) as usize;
+        let pixels = frame
+            .chunks_exact(width as usize)
+            .skip(self.overscan.top as usize)
+            .take(visible_height as usize)
+            .flat_map(|row| &row[left..left + crop_width])
+            .copied()
+            .collect();
+        Image {
+            width: crop_width,
+            height: visible_height as usize,
+            pixels,
+        }
+    }
+
+    /// Draws the last frame through the shaders again, at the size it has in the window, and
+    /// reads it back.
+    pub fn capture(&mut self) -> Result<Image, String> {
+        let swap_red_blue = match self.config.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("Unable to capture the window's format {format:?}")),
+        };
+
+        let (width, height) = self.picture_pixels();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some("Screen Texture"),
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Capture Texture"),
             view_formats: &[],
         });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let screen_texture_view =
-            screen_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let screen_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        // Rows copied out of a texture have to be padded to a multiple of 256 bytes.
+        let row_bytes = BYTES_PER_PIXEL as u32 * width;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_row_bytes * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
         });
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: None,
-            });
-        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&screen_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&screen_sampler),
-                },
-            ],
-            label: None,
-        });
-
-        let shader = device.create_shader_module(wgpu::include_wgsl!("screen_shader.wgsl"));
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &window_bind_group_layout],
-                push_constant_ranges: &[],
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
             });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[vertex::Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
+        self.post_process
+            .render(&self.queue, &mut encoder, &view, [1.0, 1.0]);
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
             },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
             },
-            multiview: None,
+            size,
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
         });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Unable to read the capture back: {e}"))?;
 
-        Ok(Self {
-            window,
-            surface,
-            device,
-            queue,
-            render_pipeline,
-            vertex_buffer,
-            uniform_buffer,
-            uniform_bind_group: window_bind_group,
-            screen_texture,
-            texture_bind_group,
-            config,
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_bytes as usize)
+            .flat_map(|row| row[..row_bytes as usize].chunks_exact(BYTES_PER_PIXEL))
+            .map(|pixel| {
+                let color = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                if swap_red_blue {
+                    (color & 0xff00ff00) | ((color >> 16) & 0xff) | ((color & 0xff) << 16)
+                } else {
+                    color
+                }
+            })
+            .collect();
+        Ok(Image {
+            width: width as usize,
+            height: height as usize,
+            pixels,
         })
     }
 
+    /// Refresh rate of the monitor the window is on, which presenting is synced to.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        let millihertz = self.window.current_monitor()?.refresh_rate_millihertz()?;
+        Some(millihertz as f64 / 1000.0)
+    }
+
     pub fn set_subtitle(&self, subtitle: &str) {
         self.window.set_title(&format!("RNES: {subtitle}"));
     }
 
+    /// Maps a cursor position in the window to the NES pixel under it, if it is over the picture.
+    pub fn screen_position(&self, position: PhysicalPosition<f64>) -> Option<(u32, u32)> {
+        let window_width = self.config.width as f64;
+        let window_height = self.config.height as f64;
+        let (width, height) = self.picture_size();
+
+        let x = (position.x - (window_width - width) / 2.0) / width;
+        let y = (position.y - (window_height - height) / 2.0) / height;
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            return None;
+        }
+
+        let (visible_width, visible_height) = self.overscan.visible_size();
+        Some((
+            self.overscan.left + (x * visible_width as f64) as u32,
+            self.overscan.top + (y * visible_height as f64) as u32,
+        ))
+    }
+
     pub fn input(&mut self, event: &WindowEvent, control_flow: &mut ControlFlow) {
         match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
@@ -270,7 +331,14 @@ impl MainWindow {
         }
     }
 
-    pub fn render(&mut self, screen_buffer: &[u32]) -> Result<(), wgpu::SurfaceError> {
+    /// Draws a frame `width` pixels wide. It is stretched to the NES's proportions, so wider
+    /// frames, such as from a filter, only add horizontal detail.
+    pub fn render(&mut self, screen_buffer: &[u32], width: u32) -> Result<(), wgpu::SurfaceError> {
+        if width != self.frame_width {
+            self.frame_width = width;
+            self.recreate_screen_texture();
+        }
+
         let output = self.surface.get_current_texture()?;
 
         let view = output
@@ -283,31 +351,8 @@ impl MainWindow {
                 label: Some("Render Encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.draw(0..(vertex::VERTICES.len() as u32), 0..1);
-        }
+        self.post_process
+            .render(&self.queue, &mut encoder, &view, self.picture_scale());
 
         self.queue.write_texture(
             wgpu::ImageCopyTexture {
@@ -316,17 +361,15 @@ impl MainWindow {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            bytemuck::cast_slice(&screen_buffer[(8 * NATIVE_RESOLUTION.width as usize)..]),
+            bytemuck::cast_slice(screen_buffer),
             wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * NATIVE_RESOLUTION.width),
-                rows_per_image: Some(NATIVE_RESOLUTION.height - 16),
-            },
-            wgpu::Extent3d {
-                width: NATIVE_RESOLUTION.width,
-                height: NATIVE_RESOLUTION.height - 16,
-                depth_or_array_layers: 1,
+                offset: (self.overscan.top * width + frame_columns(self.overscan.left, width))
+                    as u64
+                    * BYTES_PER_PIXEL as u64,
+                bytes_per_row: Some(BYTES_PER_PIXEL as u32 * width),
+                rows_per_image: Some(self.screen_texture.height()),
             },
+            self.screen_texture.size(),
         );
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -340,14 +383,74 @@ impl MainWindow {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.queue.write_buffer(
-                &self.uniform_buffer,
-                0,
-                bytemuck::cast_slice(&[WindowUniform::new(
-                    NATIVE_RESOLUTION.width as f32 / NATIVE_RESOLUTION.height as f32,
-                    self.window.inner_size().width as f32 / self.window.inner_size().height as f32,
-                )]),
-            )
+            self.resize_post_process();
         }
     }
+
+    // Size of the picture in the window, as large as fits while keeping its proportions.
+    fn picture_size(&self) -> (f64, f64) {
+        let (visible_width, visible_height) = self.overscan.visible_size();
+        let width = visible_width as f64 * self.pixel_aspect.ratio();
+        let height = visible_height as f64;
+        let scale = (self.config.width as f64 / width).min(self.config.height as f64 / height);
+        let scale = if self.integer_scaling {
+            scale.floor().max(1.0)
+        } else {
+            scale
+        };
+        (width * scale, height * scale)
+    }
+
+    // The picture's size as a fraction of the window's.
+    fn picture_scale(&self) -> [f32; 2] {
+        let (width, height) = self.picture_size();
+        [
+            (width / self.config.width as f64) as f32,
+            (height / self.config.height as f64) as f32,
+        ]
+    }
+
+    // The picture's size in whole pixels.
+    fn picture_pixels(&self) -> (u32, u32) {
+        let (width, height) = self.picture_size();
+        (
+            (width.round() as u32).max(1),
+            (height.round() as u32).max(1),
+        )
+    }
+
+    fn recreate_screen_texture(&mut self) {
+        let (visible_width, visible_height) = self.overscan.visible_size();
+        let width = frame_columns(visible_width, self.frame_width);
+        self.screen_texture = create_screen_texture(&self.device, (width, visible_height));
+        self.resize_post_process();
+    }
+
+    fn resize_post_process(&mut self) {
+        self.post_process
+            .resize(&self.device, &self.screen_texture, self.picture_pixels());
+    }
+}
+
+// NES columns in pixels of a frame `frame_width` wide, which has more of them when wider.
+fn frame_columns(columns: u32, frame_width: u32) -> u32 {
+    columns * frame_width / NATIVE_RESOLUTION.width
+}
+
+// The texture the part of the picture not cropped by overscan is uploaded to.
+fn create_screen_texture(device: &Device, (width, height): (u32, u32)) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some("Screen Texture"),
+        view_formats: &[],
+    })
 }
@@ -0,0 +1,382 @@
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue,
+    RenderPipeline, Sampler, Texture, TextureFormat, TextureView,
+};
+
+use super::{
+    shader::{ShaderFilter, ShaderPreset},
+    uniform::{shader_size, WindowUniform},
+    vertex,
+};
+
+const PRELUDE: &str = include_str!("shaders/prelude.wgsl");
+// Passes other than the last draw into textures of this format.
+const INTERMEDIATE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// The shader passes between the screen texture and the window. Each pass draws the output of
+/// the one before it, and the last draws to the window.
+pub struct PostProcessChain {
+    surface_format: TextureFormat,
+    texture_bind_group_layout: BindGroupLayout,
+    uniform_bind_group_layout: BindGroupLayout,
+    vertex_buffer: Buffer,
+    passes: Vec<PostProcessPass>,
+    // Rebuilt by `resize`, one per pass.
+    targets: Vec<PassTarget>,
+    original_size: (u32, u32),
+    frame_count: u32,
+}
+
+struct PostProcessPass {
+    pipeline: RenderPipeline,
+    sampler: Sampler,
+    scale: Option<f32>,
+    parameters: Vec<(String, f32)>,
+    uniform_buffer: Buffer,
+    uniform_bind_group: BindGroup,
+}
+
+struct PassTarget {
+    input: BindGroup,
+    // None for the last pass, which draws to the window.
+    output: Option<(Texture, TextureView)>,
+    source_size: (u32, u32),
+    output_size: (u32, u32),
+}
+
+impl PostProcessChain {
+    pub fn new(device: &Device, surface_format: TextureFormat) -> Self {
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        // This should match the filterable field of the
+                        // corresponding Texture entry above.
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: None,
+            });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Screen Vertices"),
+            contents: bytemuck::cast_slice(vertex::VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        Self {
+            surface_format,
+            texture_bind_group_layout,
+            uniform_bind_group_layout,
+            vertex_buffer,
+            passes: Vec::new(),
+            targets: Vec::new(),
+            original_size: (0, 0),
+            frame_count: 0,
+        }
+    }
+
+    /// Compiles the preset's shaders and replaces the current passes with them. Nothing changes
+    /// if any of them fails. `resize` must be called afterwards.
+    pub fn set_preset(&mut self, device: &Device, preset: &ShaderPreset) -> Result<(), String> {
+        if preset.passes.is_empty() {
+            return Err("The shader preset has no passes".to_string());
+        }
+
+        let last = preset.passes.len() - 1;
+        let passes = preset
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let format = if index == last {
+                    self.surface_format
+                } else {
+                    INTERMEDIATE_FORMAT
+                };
+                let pipeline = self
+                    .create_pipeline(device, &pass.source, format)
+                    .map_err(|e| format!("Unable to compile shader {}: {e}", pass.shader))?;
+
+                let filter = match pass.filter {
+                    ShaderFilter::Nearest => wgpu::FilterMode::Nearest,
+                    ShaderFilter::Linear => wgpu::FilterMode::Linear,
+                };
+                let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: filter,
+                    min_filter: filter,
+                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    ..Default::default()
+                });
+
+                let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Window Uniform Buffer"),
+                    contents: bytemuck::cast_slice(&[WindowUniform::new([1.0, 1.0])]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.uniform_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    }],
+                    label: None,
+                });
+
+                Ok(PostProcessPass {
+                    pipeline,
+                    sampler,
+                    scale: pass.scale.filter(|_| index != last),
+                    parameters: pass.parameters.clone(),
+                    uniform_buffer,
+                    uniform_bind_group,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        self.passes = passes;
+        self.targets.clear();
+        Ok(())
+    }
+
+    /// Sets a parameter in every pass that has it. Returns false if none do.
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        let mut found = false;
+        for pass in &mut self.passes {
+            for (_, v) in pass.parameters.iter_mut().filter(|(n, _)| n == name) {
+                *v = value;
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// Recreates the textures between passes for a new screen texture or picture size on screen.
+    pub fn resize(&mut self, device: &Device, screen: &Texture, picture_size: (u32, u32)) {
+        let max_size = device.limits().max_texture_dimension_2d;
+        self.original_size = (screen.width(), screen.height());
+
+        let screen_view = screen.create_view(&wgpu::TextureViewDescriptor::default());
+        let last = self.passes.len().saturating_sub(1);
+        let mut targets: Vec<PassTarget> = Vec::with_capacity(self.passes.len());
+        for (index, pass) in self.passes.iter().enumerate() {
+            let (source_view, source_size) = match targets.last() {
+                Some(PassTarget {
+                    output: Some((_, view)),
+                    output_size,
+                    ..
+                }) => (view, *output_size),
+                _ => (&screen_view, self.original_size),
+            };
+            let input = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&pass.sampler),
+                    },
+                ],
+                label: None,
+            });
+
+            let output_size = match pass.scale {
+                Some(scale) => (
+                    ((source_size.0 as f32 * scale).round() as u32).clamp(1, max_size),
+                    ((source_size.1 as f32 * scale).round() as u32).clamp(1, max_size),
+                ),
+                None => picture_size,
+            };
+            let output = (index != last).then(|| {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    size: wgpu::Extent3d {
+                        width: output_size.0,
+                        height: output_size.1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: INTERMEDIATE_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    label: Some("Post-processing Texture"),
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (texture, view)
+            });
+
+            targets.push(PassTarget {
+                input,
+                output,
+                source_size,
+                output_size,
+            });
+        }
+        self.targets = targets;
+    }
+
+    /// Records every pass, the last drawing the picture into the middle of `surface`, covering
+    /// `picture_scale` of its width and height.
+    pub fn render(
+        &mut self,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        surface: &TextureView,
+        picture_scale: [f32; 2],
+    ) {
+        self.frame_count = self.frame_count.wrapping_add(1);
+        let last = self.passes.len().saturating_sub(1);
+        for (index, (pass, target)) in self.passes.iter().zip(&self.targets).enumerate() {
+            let mut uniform = if index == last {
+                WindowUniform::new(picture_scale)
+            } else {
+                WindowUniform::new([1.0, 1.0])
+            };
+            uniform.frame_count = self.frame_count;
+            uniform.original_size = shader_size(self.original_size);
+            uniform.source_size = shader_size(target.source_size);
+            uniform.output_size = shader_size(target.output_size);
+            for (value, (_, parameter)) in uniform.parameters.iter_mut().zip(&pass.parameters) {
+                *value = *parameter;
+            }
+            queue.write_buffer(&pass.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+            let view = target.output.as_ref().map_or(surface, |(_, view)| view);
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.0,
+                            g: 0.0,
+                            b: 0.0,
+                            a: 1.0,
+                        }),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&pass.pipeline);
+            render_pass.set_bind_group(0, &target.input, &[]);
+            render_pass.set_bind_group(1, &pass.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.draw(0..(vertex::VERTICES.len() as u32), 0..1);
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        source: &str,
+        format: TextureFormat,
+    ) -> Result<RenderPipeline, String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post-processing Shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{PRELUDE}\n{source}").into()),
+        });
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &self.texture_bind_group_layout,
+                    &self.uniform_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[vertex::Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        match now_or_never(device.pop_error_scope()) {
+            Some(Some(error)) => Err(error.to_string()),
+            _ => Ok(render_pipeline),
+        }
+    }
+}
+
+// Native backends report errors as they happen, so the future is already finished.
+fn now_or_never<T>(future: impl Future<Output = T>) -> Option<T> {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(value) => Some(value),
+        Poll::Pending => None,
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+
+/// How many `#parameter`s one shader can declare.
+pub const MAX_PARAMETERS: usize = 16;
+
+/// Presets that are built in, in the order the shader hotkey cycles through them.
+pub const BUILTIN_PRESETS: [&str; 6] = [
+    "nearest",
+    "bilinear",
+    "sharp-bilinear",
+    "crt",
+    "xbr",
+    "xbr-crt",
+];
+
+/// A chain of shader passes run on the picture before it reaches the window, read from a TOML
+/// file of `[[pass]]` tables and an optional `[parameters]` table.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShaderPreset {
+    #[serde(rename = "pass")]
+    pub passes: Vec<ShaderPass>,
+    #[serde(default)]
+    pub parameters: HashMap<String, f32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShaderPass {
+    /// A built-in shader's name or a WGSL file, relative to the preset.
+    pub shader: String,
+    /// How the pass samples the previous pass's output.
+    #[serde(default)]
+    pub filter: ShaderFilter,
+    /// Size of the output relative to the input. Without it the pass draws at the size the
+    /// picture has on screen, as the last pass always does.
+    pub scale: Option<f32>,
+    #[serde(skip)]
+    pub(super) source: String,
+    // The shader's `#parameter`s and their values.
+    #[serde(skip)]
+    pub(super) parameters: Vec<(String, f32)>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShaderFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl Default for ShaderPreset {
+    fn default() -> Self {
+        Self::builtin(BUILTIN_PRESETS[0]).unwrap()
+    }
+}
+
+impl ShaderPreset {
+    /// A built-in preset by name, or else a preset file.
+    pub fn open(name: &str) -> Result<Self, String> {
+        match Self::builtin(name) {
+            Some(preset) => Ok(preset),
+            None => Self::load(Path::new(name)),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        use ShaderFilter::*;
+        let passes = match name {
+            "nearest" => vec![("stock", Nearest, None)],
+            "bilinear" => vec![("stock", Linear, None)],
+            "sharp-bilinear" => vec![("sharp-bilinear", Linear, None)],
+            "crt" => vec![("crt", Linear, None)],
+            "xbr" => vec![("xbr", Nearest, None)],
+            "xbr-crt" => vec![("xbr", Nearest, Some(2.0)), ("crt", Linear, None)],
+            _ => return None,
+        };
+        let passes = passes
+            .into_iter()
+            .map(|(shader, filter, scale)| {
+                let source = builtin_shader(shader).unwrap().to_string();
+                ShaderPass {
+                    shader: shader.to_string(),
+                    filter,
+                    scale,
+                    parameters: declared_parameters(&source).unwrap(),
+                    source,
+                }
+            })
+            .collect();
+        Some(Self {
+            passes,
+            parameters: HashMap::new(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read shader preset {}: {e}", path.display()))?;
+        let mut preset: Self = toml::from_str(&text)
+            .map_err(|e| format!("Invalid shader preset {}: {e}", path.display()))?;
+        if preset.passes.is_empty() {
+            return Err(format!("Shader preset {} has no passes", path.display()));
+        }
+
+        let directory = path.parent().unwrap_or(Path::new("."));
+        for pass in &mut preset.passes {
+            pass.source = match builtin_shader(&pass.shader) {
+                Some(source) => source.to_string(),
+                None => {
+                    let shader_path = directory.join(&pass.shader);
+                    fs::read_to_string(&shader_path).map_err(|e| {
+                        format!("Unable to read shader {}: {e}", shader_path.display())
+                    })?
+                }
+            };
+            pass.parameters = declared_parameters(&pass.source)
+                .map_err(|e| format!("Invalid shader {}: {e}", pass.shader))?;
+        }
+
+        for (name, value) in &preset.parameters {
+            let mut found = false;
+            for pass in &mut preset.passes {
+                found |= pass.set_parameter(name, *value);
+            }
+            if !found {
+                return Err(format!("No shader in the preset has a parameter {name}"));
+            }
+        }
+        Ok(preset)
+    }
+}
+
+impl ShaderPass {
+    pub(super) fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match self.parameters.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => {
+                *v = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn builtin_shader(name: &str) -> Option<&'static str> {
+    match name {
+        "stock" => Some(include_str!("shaders/stock.wgsl")),
+        "sharp-bilinear" => Some(include_str!("shaders/sharp_bilinear.wgsl")),
+        "crt" => Some(include_str!("shaders/crt.wgsl")),
+        "xbr" => Some(include_str!("shaders/xbr.wgsl")),
+        _ => None,
+    }
+}
+
+// Shaders declare their parameters with `// #parameter <name> <default>` lines, which number them
+// in order for the `parameter` function.
+fn declared_parameters(source: &str) -> Result<Vec<(String, f32)>, String> {
+    let parameters = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("// #parameter"))
+        .map(|declaration| {
+            let mut words = declaration.split_whitespace();
+            match (words.next(), words.next().map(str::parse::<f32>)) {
+                (Some(name), Some(Ok(default))) => Ok((name.to_string(), default)),
+                _ => Err(format!("Malformed parameter \"{}\"", declaration.trim())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if parameters.len() > MAX_PARAMETERS {
+        return Err(format!("More than {MAX_PARAMETERS} parameters"));
+    }
+    Ok(parameters)
+}
@@ -0,0 +1,26 @@
+// A CRT television: a bulging screen, dark gaps between the scanlines and an aperture grille.
+// Scanlines follow the original picture, so this works after passes that scale it up.
+
+// #parameter curvature 0.06
+// #parameter scanline_strength 0.45
+// #parameter mask_strength 0.2
+// #parameter brightness 1.2
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let centered = in.tex_coords * 2.0 - 1.0;
+  let curved = (centered + centered * centered.yx * centered.yx * parameter(0u)) * 0.5 + 0.5;
+  let color = textureSample(t_source, s_source, curved).rgb;
+
+  // The beam is brightest in the middle of each line.
+  let line_position = fract(curved.y * window.original_size.y) - 0.5;
+  let scanline = 1.0 - parameter(1u) * line_position * line_position * 4.0;
+
+  // Every third column of the window lights only one of red, green and blue at full strength.
+  var mask = vec3<f32>(1.0 - parameter(2u));
+  mask[u32(in.clip_position.x) % 3u] = 1.0;
+
+  let inside = all(curved >= vec2<f32>(0.0)) && all(curved <= vec2<f32>(1.0));
+  let shaded = color * scanline * mask * parameter(3u);
+  return vec4<f32>(select(vec3<f32>(0.0), shaded, inside), 1.0);
+}
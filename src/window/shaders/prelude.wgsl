@@ -0,0 +1,49 @@
+// Put in front of every post-processing shader, which then only has to provide `fs_main`.
+
+struct WindowUniform {
+  // How much of the window's width and height the picture covers.
+  picture_scale: vec2<f32>,
+  frame_count: u32,
+  padding: u32,
+  // Sizes are width, height, 1 / width and 1 / height in pixels. The original is the NES picture
+  // before any pass, the source this pass's input.
+  original_size: vec4<f32>,
+  source_size: vec4<f32>,
+  output_size: vec4<f32>,
+  parameters: array<vec4<f32>, 4>
+};
+@group(1) @binding(0)
+var<uniform> window: WindowUniform;
+
+struct VertexInput {
+  @location(0) position: vec3<f32>
+}
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>
+};
+
+@vertex
+fn vs_main(screen_coords: VertexInput) -> VertexOutput {
+  var out: VertexOutput;
+
+  out.clip_position = vec4<f32>(
+      screen_coords.position.x * window.picture_scale.x,
+      screen_coords.position.y * window.picture_scale.y,
+      screen_coords.position.z,
+      1.0);
+  out.tex_coords = (screen_coords.position.xy + 1.0) * 0.5;
+  out.tex_coords.y = (1.0 - out.tex_coords.y);
+  return out;
+}
+
+@group(0) @binding(0)
+  var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+  var s_source: sampler;
+
+// The value of the shader's `index`th `#parameter`.
+fn parameter(index: u32) -> f32 {
+  return window.parameters[index / 4u][index % 4u];
+}
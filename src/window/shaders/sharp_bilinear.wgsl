@@ -0,0 +1,16 @@
+// Scales up by the largest whole number that fits with nearest sampling, then the rest of the way
+// with bilinear, so pixels stay sharp without uneven sizes. Needs linear sampling.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let texel = in.tex_coords * window.source_size.xy;
+  let scale = max(floor(window.output_size.xy * window.source_size.zw), vec2<f32>(1.0));
+
+  // Only the edge of each texel, a pixel wide after scaling, is blended with its neighbour.
+  let region_range = 0.5 - 0.5 / scale;
+  let center_distance = fract(texel) - 0.5;
+  let offset =
+    (center_distance - clamp(center_distance, -region_range, region_range)) * scale + 0.5;
+
+  return textureSample(t_source, s_source, (floor(texel) + offset) * window.source_size.zw);
+}
@@ -0,0 +1,6 @@
+// Draws the source as it is, filtered however the pass samples it.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(t_source, s_source, in.tex_coords);
+}
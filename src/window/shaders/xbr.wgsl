@@ -0,0 +1,53 @@
+// Edge-directed scaling in the style of xBR. Where two neighbours of a pixel continue a diagonal
+// edge past one of its corners, that corner is cut along the edge instead of drawn square.
+//
+//   A B C
+//   D E F    E is the source pixel being drawn, F, H and I the neighbours towards the corner
+//   G H I    the output pixel is in, mirrored as needed.
+
+// #parameter edge_softness 0.15
+
+// Color difference weighted the way xBR does, on YUV.
+fn difference(a: vec3<f32>, b: vec3<f32>) -> f32 {
+  let yuv = mat3x3<f32>(
+    vec3<f32>(0.299, -0.169, 0.499),
+    vec3<f32>(0.587, -0.331, -0.418),
+    vec3<f32>(0.114, 0.499, -0.0813));
+  return dot(abs(yuv * (a - b)), vec3<f32>(48.0, 7.0, 6.0));
+}
+
+fn load(texel: vec2<i32>) -> vec3<f32> {
+  let size = vec2<i32>(textureDimensions(t_source));
+  return textureLoad(t_source, clamp(texel, vec2<i32>(0), size - 1), 0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let position = in.tex_coords * window.source_size.xy;
+  let texel = vec2<i32>(floor(position));
+  let offset = fract(position) - 0.5;
+  let dx = vec2<i32>(select(-1, 1, offset.x >= 0.0), 0);
+  let dy = vec2<i32>(0, select(-1, 1, offset.y >= 0.0));
+
+  let b = load(texel - dy);
+  let c = load(texel + dx - dy);
+  let d = load(texel - dx);
+  let e = load(texel);
+  let f = load(texel + dx);
+  let g = load(texel - dx + dy);
+  let h = load(texel + dy);
+  let i = load(texel + dx + dy);
+
+  // How much an edge along F-H costs against one along E-I.
+  let across = difference(e, c) + difference(e, g) + 4.0 * difference(f, h);
+  let along = difference(d, h) + difference(b, f) + 4.0 * difference(e, i);
+
+  // The corner is the part of the pixel past the line between the middles of its two sides.
+  let corner = abs(offset.x) + abs(offset.y);
+  let softness = max(parameter(0u), 0.001);
+  let blend = smoothstep(0.5 - softness, 0.5 + softness, corner);
+  let edge = select(h, f, difference(e, f) <= difference(e, h));
+
+  let color = select(e, mix(e, edge, blend), across < along);
+  return vec4<f32>(color, 1.0);
+}
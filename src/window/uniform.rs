@@ -1,17 +1,36 @@
 #![allow(dead_code)]
 
+use super::shader::MAX_PARAMETERS;
+
+/// Matches `WindowUniform` in `shaders/prelude.wgsl`.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct WindowUniform {
-    region_aspect: f32,
-    window_aspect: f32,
+    pub picture_scale: [f32; 2],
+    pub frame_count: u32,
+    padding: u32,
+    pub original_size: [f32; 4],
+    pub source_size: [f32; 4],
+    pub output_size: [f32; 4],
+    pub parameters: [f32; MAX_PARAMETERS],
 }
 
 impl WindowUniform {
-    pub fn new(region_aspect: f32, window_aspect: f32) -> Self {
+    pub fn new(picture_scale: [f32; 2]) -> Self {
         Self {
-            region_aspect,
-            window_aspect,
+            picture_scale,
+            frame_count: 0,
+            padding: 0,
+            original_size: [0.0; 4],
+            source_size: [0.0; 4],
+            output_size: [0.0; 4],
+            parameters: [0.0; MAX_PARAMETERS],
         }
     }
 }
+
+/// A size as the shaders take it: width, height and their reciprocals.
+pub fn shader_size((width, height): (u32, u32)) -> [f32; 4] {
+    let (width, height) = (width as f32, height as f32);
+    [width, height, 1.0 / width, 1.0 / height]
+}